@@ -1,4 +1,12 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { RenderPurpose } from "./RenderPurpose";
 
-export type CallTemplateFunctionArgs = { purpose: RenderPurpose, values: { [key: string]: string }, };
+export type CallTemplateFunctionArgs = { purpose: RenderPurpose, 
+/**
+ * Arg values as strings, with numbers, lists, and objects encoded as JSON
+ */
+values: { [key: string]: string }, 
+/**
+ * The same arg values, but keeping their types
+ */
+typedValues: Record<string, any>, };
@@ -1,4 +1,12 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { EnvironmentVariable } from "./EnvironmentVariable";
 
-export type Environment = { id: string, workspaceId: string, model: "environment", createdAt: string, updatedAt: string, name: string, variables: Array<EnvironmentVariable>, };
+export type Environment = { id: string, workspaceId: string, model: "environment", createdAt: string, updatedAt: string, name: string, variables: Array<EnvironmentVariable>, 
+/**
+ * Parent environment to inherit variables from
+ */
+environmentId: string | null, 
+/**
+ * Paths of `.env` files whose variables are merged into this environment
+ */
+envFiles: Array<string>, };
@@ -1,3 +1,13 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { EnvironmentVariableType } from "./EnvironmentVariableType";
 
-export type EnvironmentVariable = { enabled?: boolean, name: string, value: string, };
+export type EnvironmentVariable = { enabled?: boolean, name: string, value: string, 
+/**
+ * Secret values are encrypted at rest, masked in previews, and left out of exports
+ */
+secret?: boolean, 
+/**
+ * What the value holds, which decides how it's validated and inlined into JSON bodies.
+ * Untyped values are inlined as-is.
+ */
+valueType?: EnvironmentVariableType, };
@@ -0,0 +1,27 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type GrpcCallOptions = { 
+/**
+ * Milliseconds the call may take, sent as `grpc-timeout`, or 0 for no deadline
+ */
+deadline: number, 
+/**
+ * Compress sent messages with gzip
+ */
+sendGzip: boolean, 
+/**
+ * Let the server compress its messages with gzip
+ */
+acceptGzip: boolean, 
+/**
+ * Largest message in bytes that may be sent, or 0 for the default
+ */
+maxSendSize: number, 
+/**
+ * Largest message in bytes that may be received, or 0 for the default
+ */
+maxReceiveSize: number, 
+/**
+ * Wait for the server to become reachable instead of failing right away
+ */
+waitForReady: boolean, };
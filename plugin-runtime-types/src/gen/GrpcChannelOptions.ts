@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type GrpcChannelOptions = { 
+/**
+ * Milliseconds between HTTP/2 keepalive pings, or 0 to not send them
+ */
+keepaliveInterval: number, 
+/**
+ * Milliseconds to wait for a ping to be acknowledged, or 0 for the default
+ */
+keepaliveTimeout: number, 
+/**
+ * Milliseconds an unused connection stays open, or 0 for the default
+ */
+idleTimeout: number, };
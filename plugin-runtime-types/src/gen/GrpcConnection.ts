@@ -1,3 +1,15 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export type GrpcConnection = { id: string, model: "grpc_connection", workspaceId: string, requestId: string, createdAt: string, updatedAt: string, service: string, method: string, elapsed: number, status: number, url: string, error: string | null, trailers: { [key: string]: string }, };
+export type GrpcConnection = { id: string, model: "grpc_connection", workspaceId: string, requestId: string, createdAt: string, updatedAt: string, service: string, method: string, elapsed: number, status: number, url: string, error: string | null, trailers: { [key: string]: string }, 
+/**
+ * Initial metadata the server responded with, before any messages
+ */
+headers: { [key: string]: string }, 
+/**
+ * Encoding the server compressed its messages with, from `grpc-encoding`
+ */
+encoding: string | null, 
+/**
+ * State of the channel after the call, like `ready` when its connection was kept open
+ */
+channelState: string | null, };
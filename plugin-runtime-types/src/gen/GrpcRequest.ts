@@ -1,4 +1,11 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { GrpcCallOptions } from "./GrpcCallOptions";
+import type { GrpcChannelOptions } from "./GrpcChannelOptions";
 import type { GrpcMetadataEntry } from "./GrpcMetadataEntry";
+import type { GrpcTlsConfig } from "./GrpcTlsConfig";
 
-export type GrpcRequest = { id: string, model: "grpc_request", workspaceId: string, createdAt: string, updatedAt: string, folderId: string | null, name: string, sortPriority: number, url: string, service: string | null, method: string | null, message: string, authenticationType: string | null, authentication: Record<string, any>, metadata: Array<GrpcMetadataEntry>, };
+export type GrpcRequest = { id: string, model: "grpc_request", workspaceId: string, createdAt: string, updatedAt: string, folderId: string | null, name: string, sortPriority: number, url: string, service: string | null, method: string | null, message: string, authenticationType: string | null, authentication: Record<string, any>, metadata: Array<GrpcMetadataEntry>, tls: GrpcTlsConfig, 
+/**
+ * Native gRPC, or gRPC-Web with binary or base64 text bodies
+ */
+protocol: "grpc" | "grpc_web" | "grpc_web_text", callOptions: GrpcCallOptions, channelOptions: GrpcChannelOptions, };
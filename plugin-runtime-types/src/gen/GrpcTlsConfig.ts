@@ -0,0 +1,27 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type GrpcTlsConfig = { 
+/**
+ * Accept any server certificate, like for self-signed servers
+ */
+skipVerify: boolean, 
+/**
+ * PEM files of CA certificates to trust, in addition to the system's
+ */
+caCertPaths: Array<string>, 
+/**
+ * PEM file of the client certificate chain, for mutual TLS
+ */
+clientCertPath: string | null, 
+/**
+ * PEM file of the client certificate's private key
+ */
+clientKeyPath: string | null, 
+/**
+ * Name to send for SNI and verify the certificate against, instead of the URL's host
+ */
+serverName: string | null, 
+/**
+ * Authority to send with requests, instead of the URL's host and port
+ */
+authority: string | null, };
@@ -1,4 +1,8 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { HttpRequest } from "./HttpRequest";
 
-export type RenderHttpRequestResponse = { httpRequest: HttpRequest, };
+export type RenderHttpRequestResponse = { httpRequest: HttpRequest, 
+/**
+ * Why the request couldn't be rendered, in which case it's returned unrendered
+ */
+error: string | null, };
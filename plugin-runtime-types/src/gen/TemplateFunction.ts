@@ -1,4 +1,10 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { TemplateFunctionArg } from "./TemplateFunctionArg";
 
-export type TemplateFunction = { name: string, args: Array<TemplateFunctionArg>, };
+export type TemplateFunction = { name: string, args: Array<TemplateFunctionArg>, 
+/**
+ * Whether calls with the same args return the same value, so they only need to run
+ * once per send. Functions that don't say are treated as deterministic, so ones like
+ * random values need to set this to false to run every time.
+ */
+deterministic?: boolean | null, };
@@ -15,6 +15,7 @@ export * from './gen/CopyTextRequest';
 export * from './gen/EmptyResponse';
 export * from './gen/Environment';
 export * from './gen/EnvironmentVariable';
+export * from './gen/EnvironmentVariableType';
 export * from './gen/ExportHttpRequestRequest';
 export * from './gen/ExportHttpRequestResponse';
 export * from './gen/FilterRequest';
@@ -26,10 +27,13 @@ export * from './gen/GetHttpRequestActionsResponse';
 export * from './gen/GetHttpRequestByIdRequest';
 export * from './gen/GetHttpRequestByIdResponse';
 export * from './gen/GetTemplateFunctionsResponse';
+export * from './gen/GrpcCallOptions';
+export * from './gen/GrpcChannelOptions';
 export * from './gen/GrpcConnection';
 export * from './gen/GrpcEvent';
 export * from './gen/GrpcMetadataEntry';
 export * from './gen/GrpcRequest';
+export * from './gen/GrpcTlsConfig';
 export * from './gen/HttpRequest';
 export * from './gen/HttpRequestAction';
 export * from './gen/HttpRequestHeader';
@@ -136,6 +136,9 @@ async function initialize() {
       async render(args) {
         const payload = { type: 'render_http_request_request', ...args } as const;
         const result = await sendAndWaitForReply<RenderHttpRequestResponse>(payload);
+        if (result.error != null) {
+          throw new Error(`Failed to render request: ${result.error}`);
+        }
         return result.httpRequest;
       },
     },
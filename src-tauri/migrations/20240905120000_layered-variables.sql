@@ -0,0 +1,2 @@
+ALTER TABLE environments ADD COLUMN environment_id TEXT NULL REFERENCES environments (id) ON DELETE SET NULL;
+ALTER TABLE folders ADD COLUMN variables TEXT DEFAULT '[]' NOT NULL;
@@ -0,0 +1 @@
+ALTER TABLE environments ADD COLUMN env_files TEXT DEFAULT '[]' NOT NULL;
@@ -0,0 +1 @@
+ALTER TABLE grpc_requests ADD COLUMN tls TEXT DEFAULT '{}' NOT NULL;
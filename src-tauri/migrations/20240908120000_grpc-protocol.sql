@@ -0,0 +1 @@
+ALTER TABLE grpc_requests ADD COLUMN protocol TEXT DEFAULT 'grpc' NOT NULL;
@@ -0,0 +1,2 @@
+ALTER TABLE grpc_requests ADD COLUMN call_options TEXT DEFAULT '{}' NOT NULL;
+ALTER TABLE grpc_connections ADD COLUMN encoding TEXT NULL;
@@ -0,0 +1,2 @@
+ALTER TABLE grpc_requests ADD COLUMN channel_options TEXT DEFAULT '{}' NOT NULL;
+ALTER TABLE grpc_connections ADD COLUMN channel_state TEXT NULL;
@@ -0,0 +1 @@
+ALTER TABLE grpc_connections ADD COLUMN headers TEXT DEFAULT '{}' NOT NULL;
@@ -0,0 +1 @@
+ALTER TABLE grpc_events ADD COLUMN error_details TEXT DEFAULT '[]' NOT NULL;
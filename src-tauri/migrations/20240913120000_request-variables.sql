@@ -0,0 +1 @@
+ALTER TABLE http_requests ADD COLUMN variables TEXT DEFAULT '[]' NOT NULL;
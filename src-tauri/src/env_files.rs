@@ -0,0 +1,186 @@
+use log::warn;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+use tokio::fs;
+use yaak_models::models::EnvironmentVariable;
+use yaak_templates::escape_template;
+
+/// Variables read from a `.env` file attached to an environment
+#[derive(Debug, Clone)]
+pub struct EnvFile {
+    pub path: String,
+    /// Values are escaped, since `.env` files hold plain values instead of templates
+    pub variables: Vec<EnvironmentVariable>,
+}
+
+// Parsed files by path, along with the modification time they were parsed at
+type EnvFileCache = HashMap<PathBuf, (SystemTime, Vec<EnvironmentVariable>)>;
+
+static CACHE: OnceLock<Mutex<EnvFileCache>> = OnceLock::new();
+
+/// Read the `.env` file from its canonical path, as the path it was attached with. There's
+/// no watcher: each load compares the file's modification time to the last parse, and only
+/// parses it again if that changed. Missing or unreadable files have no variables.
+pub async fn load_env_file(path: &str, canonical: &Path) -> EnvFile {
+    let variables = match read_variables(canonical.to_path_buf()).await {
+        Ok(v) => v,
+        Err(e) => {
+            warn!("Failed to read env file {path}: {e}");
+            Vec::new()
+        }
+    };
+    let variables = variables
+        .into_iter()
+        .map(|v| EnvironmentVariable {
+            value: escape_template(&v.value),
+            ..v
+        })
+        .collect();
+
+    EnvFile {
+        path: path.to_string(),
+        variables,
+    }
+}
+
+async fn read_variables(path: PathBuf) -> std::io::Result<Vec<EnvironmentVariable>> {
+    let modified = fs::metadata(&path).await?.modified()?;
+    let cache = CACHE.get_or_init(|| Mutex::new(HashMap::new()));
+
+    if let Some((cached_modified, variables)) = cache.lock().unwrap().get(&path) {
+        if *cached_modified == modified {
+            return Ok(variables.clone());
+        }
+    }
+
+    let variables = parse_env_file(fs::read_to_string(&path).await?.as_str());
+    cache
+        .lock()
+        .unwrap()
+        .insert(path, (modified, variables.clone()));
+    Ok(variables)
+}
+
+/// Parse `KEY=value` lines, allowing `export` prefixes, `#` comments, and single- or
+/// double-quoted values. Double-quoted values may span lines and use `\n` style escapes.
+pub fn parse_env_file(contents: &str) -> Vec<EnvironmentVariable> {
+    let mut variables = Vec::new();
+    let mut lines = contents.lines();
+    while let Some(line) = lines.next() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (name, rest) = match line.split_once('=') {
+            Some((name, rest)) => (name.trim(), rest.trim_start()),
+            None => continue,
+        };
+        if name.is_empty() {
+            continue;
+        }
+
+        let value = if let Some(rest) = rest.strip_prefix('"') {
+            let mut raw = rest.to_string();
+            while !has_closing_quote(&raw) {
+                match lines.next() {
+                    Some(next) => {
+                        raw.push('\n');
+                        raw.push_str(next);
+                    }
+                    None => break,
+                }
+            }
+            unescape(&raw)
+        } else if let Some(rest) = rest.strip_prefix('\'') {
+            match rest.find('\'') {
+                Some(end) => rest[..end].to_string(),
+                None => rest.to_string(),
+            }
+        } else {
+            match rest.find(" #") {
+                Some(end) => rest[..end].trim_end().to_string(),
+                None => rest.trim_end().to_string(),
+            }
+        };
+
+        variables.push(EnvironmentVariable {
+            enabled: true,
+            name: name.to_string(),
+            value,
+            ..Default::default()
+        });
+    }
+
+    variables
+}
+
+fn has_closing_quote(raw: &str) -> bool {
+    let mut escaped = false;
+    for ch in raw.chars() {
+        match ch {
+            '\\' if !escaped => escaped = true,
+            '"' if !escaped => return true,
+            _ => escaped = false,
+        }
+    }
+    false
+}
+
+/// Unescape a double-quoted value, ignoring anything after the closing quote
+fn unescape(raw: &str) -> String {
+    let mut value = String::new();
+    let mut chars = raw.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '"' => break,
+            '\\' => match chars.next() {
+                Some('n') => value.push('\n'),
+                Some('r') => value.push('\r'),
+                Some('t') => value.push('\t'),
+                Some(c) => value.push(c),
+                None => value.push('\\'),
+            },
+            c => value.push(c),
+        }
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::env_files::parse_env_file;
+
+    #[test]
+    fn parse() {
+        let contents = r#"
+# Comment
+export TOKEN=abc123
+PLAIN = hello world # trailing comment
+SINGLE='raw \n value'
+DOUBLE="line\nbreak \"quoted\""
+MULTI="first
+second"
+EMPTY=
+not a variable
+"#;
+        let variables: Vec<(String, String)> = parse_env_file(contents)
+            .into_iter()
+            .map(|v| (v.name, v.value))
+            .collect();
+        assert_eq!(
+            variables,
+            vec![
+                ("TOKEN".to_string(), "abc123".to_string()),
+                ("PLAIN".to_string(), "hello world".to_string()),
+                ("SINGLE".to_string(), "raw \\n value".to_string()),
+                ("DOUBLE".to_string(), "line\nbreak \"quoted\"".to_string()),
+                ("MULTI".to_string(), "first\nsecond".to_string()),
+                ("EMPTY".to_string(), "".to_string()),
+            ]
+        );
+    }
+}
@@ -2,6 +2,7 @@ use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 use tauri::{Manager, WebviewWindow};
 use yaak_models::models::{Environment, Folder, GrpcRequest, HttpRequest, Workspace};
+use yaak_models::secrets::redact_variables;
 
 #[derive(Default, Debug, Deserialize, Serialize)]
 #[serde(default, rename_all = "camelCase")]
@@ -46,21 +47,26 @@ pub async fn get_workspace_export_resources(
     };
 
     for workspace_id in workspace_ids {
-        data.resources.workspaces.push(
-            yaak_models::queries::get_workspace(window, workspace_id)
-                .await
-                .expect("Failed to get workspace"),
-        );
-        data.resources.environments.append(
-            &mut yaak_models::queries::list_environments(window, workspace_id)
-                .await
-                .expect("Failed to get environments"),
-        );
-        data.resources.folders.append(
-            &mut yaak_models::queries::list_folders(window, workspace_id)
-                .await
-                .expect("Failed to get folders"),
-        );
+        let mut workspace = yaak_models::queries::get_workspace(window, workspace_id)
+            .await
+            .expect("Failed to get workspace");
+        redact_variables(&mut workspace.variables);
+        data.resources.workspaces.push(workspace);
+
+        let mut environments = yaak_models::queries::list_environments(window, workspace_id)
+            .await
+            .expect("Failed to get environments");
+        for e in environments.iter_mut() {
+            redact_variables(&mut e.variables);
+        }
+        data.resources.environments.append(&mut environments);
+        let mut folders = yaak_models::queries::list_folders(window, workspace_id)
+            .await
+            .expect("Failed to get folders");
+        for f in folders.iter_mut() {
+            redact_variables(&mut f.variables);
+        }
+        data.resources.folders.append(&mut folders);
         data.resources.http_requests.append(
             &mut yaak_models::queries::list_http_requests(window, workspace_id)
                 .await
@@ -1,16 +1,213 @@
 use std::collections::HashMap;
+use std::time::Duration;
 
+use base64::Engine;
+use log::warn;
+use tauri::WebviewWindow;
 use KeyAndValueRef::{Ascii, Binary};
 
-use yaak_grpc::{KeyAndValueRef, MetadataMap};
+use yaak_grpc::{CallOptions, ChannelConfig, KeyAndValueRef, MetadataMap, TlsConfig};
+use yaak_models::models::{GrpcConnection, GrpcRequest, Workspace};
+use yaak_models::queries::{get_grpc_connection, upsert_grpc_connection};
 
+/// Metadata as strings, with the values of binary `-bin` keys base64 decoded when they're text
 pub fn metadata_to_map(metadata: MetadataMap) -> HashMap<String, String> {
     let mut entries = HashMap::new();
     for r in metadata.iter() {
         match r {
-            Ascii(k, v) => entries.insert(k.to_string(), v.to_str().unwrap().to_string()),
-            Binary(k, v) => entries.insert(k.to_string(), format!("{:?}", v)),
+            // Servers may send header values with bytes that aren't visible ASCII
+            Ascii(k, v) => entries.insert(
+                k.to_string(),
+                String::from_utf8_lossy(v.as_encoded_bytes()).to_string(),
+            ),
+            Binary(k, v) => {
+                // Values that aren't text, or weren't encoded properly, are kept base64
+                // encoded as they were sent
+                let value = match v.to_bytes().map(|b| String::from_utf8(b.to_vec())) {
+                    Ok(Ok(text)) => text,
+                    _ => String::from_utf8_lossy(v.as_encoded_bytes()).to_string(),
+                };
+                entries.insert(k.to_string(), value)
+            }
         };
     }
     entries
 }
+
+/// How to connect for the request. TLS verification is skipped if the workspace doesn't
+/// validate certificates.
+pub fn channel_config(req: &GrpcRequest, workspace: &Workspace) -> ChannelConfig {
+    let mut tls = TlsConfig::from(&req.tls);
+    tls.skip_verify = tls.skip_verify || !workspace.setting_validate_certificates;
+    let o = &req.channel_options;
+    let millis = |v: i32| match v > 0 {
+        true => Some(Duration::from_millis(v as u64)),
+        false => None,
+    };
+    ChannelConfig {
+        // Requests from before the protocol could be chosen have it empty
+        protocol: serde_json::from_value(req.protocol.clone().into()).unwrap_or_default(),
+        tls,
+        keepalive_interval: millis(o.keepalive_interval),
+        keepalive_timeout: millis(o.keepalive_timeout),
+        idle_timeout: millis(o.idle_timeout),
+    }
+}
+
+/// Metadata to send with a rendered request, including its authentication
+pub fn build_metadata(req: &GrpcRequest) -> HashMap<String, String> {
+    let mut metadata = HashMap::new();
+
+    // Add the rest of metadata
+    for h in req.clone().metadata {
+        if h.name.is_empty() && h.value.is_empty() {
+            continue;
+        }
+
+        if !h.enabled {
+            continue;
+        }
+
+        metadata.insert(h.name, h.value);
+    }
+
+    if let Some(b) = &req.authentication_type {
+        let req = req.clone();
+        let empty_value = &serde_json::to_value("").unwrap();
+        let a = req.authentication;
+
+        if b == "basic" {
+            let username = a
+                .get("username")
+                .unwrap_or(empty_value)
+                .as_str()
+                .unwrap_or("");
+            let password = a
+                .get("password")
+                .unwrap_or(empty_value)
+                .as_str()
+                .unwrap_or("");
+
+            let auth = format!("{username}:{password}");
+            let encoded = base64::engine::general_purpose::STANDARD_NO_PAD.encode(auth);
+            metadata.insert("Authorization".to_string(), format!("Basic {}", encoded));
+        } else if b == "bearer" {
+            let token = a.get("token").unwrap_or(empty_value).as_str().unwrap_or("");
+            metadata.insert("Authorization".to_string(), format!("Bearer {token}"));
+        }
+    }
+
+    metadata
+}
+
+/// Options for each call the request makes, where zero values mean the defaults
+pub fn call_options(req: &GrpcRequest) -> CallOptions {
+    let o = &req.call_options;
+    let positive = |v: i32| if v > 0 { Some(v as usize) } else { None };
+    CallOptions {
+        deadline: positive(o.deadline).map(|ms| Duration::from_millis(ms as u64)),
+        send_gzip: o.send_gzip,
+        accept_gzip: o.accept_gzip,
+        max_send_size: positive(o.max_send_size),
+        max_receive_size: positive(o.max_receive_size),
+        wait_for_ready: o.wait_for_ready,
+    }
+}
+
+/// Record the server's initial metadata on the connection, along with the encoding it
+/// compressed its messages with
+pub async fn set_connection_headers(w: &WebviewWindow, conn_id: &str, metadata: &MetadataMap) {
+    let conn = match get_grpc_connection(w, conn_id).await {
+        Ok(c) => c,
+        Err(e) => {
+            warn!("Failed to get gRPC connection {}: {}", conn_id, e);
+            return;
+        }
+    };
+    let conn = GrpcConnection {
+        headers: metadata_to_map(metadata.clone()),
+        encoding: Some(response_encoding(metadata)),
+        ..conn
+    };
+    if let Err(e) = upsert_grpc_connection(w, &conn).await {
+        warn!("Failed to update gRPC connection {}: {}", conn_id, e);
+    }
+}
+
+/// Encoding the server compressed its messages with, which is `identity` if it didn't say
+fn response_encoding(metadata: &MetadataMap) -> String {
+    metadata
+        .get("grpc-encoding")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("identity")
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use yaak_grpc::{MetadataMap, MetadataValue};
+    use yaak_models::models::{GrpcCallOptions, GrpcRequest};
+
+    use crate::grpc::{call_options, metadata_to_map};
+
+    #[test]
+    fn binary_metadata() {
+        let mut metadata = MetadataMap::new();
+        metadata.insert("x-text", "hello".parse().unwrap());
+        metadata.insert(
+            "x-obs-text",
+            MetadataValue::try_from(&b"caf\xe9"[..]).unwrap(),
+        );
+        metadata.insert_bin("x-text-bin", MetadataValue::from_bytes(b"hello"));
+        metadata.insert_bin("x-raw-bin", MetadataValue::from_bytes(&[0xff, 0xfe, 0x00]));
+
+        let map = metadata_to_map(metadata);
+        assert_eq!(map.get("x-text").unwrap(), "hello");
+        assert_eq!(map.get("x-obs-text").unwrap(), "caf\u{fffd}");
+        assert_eq!(map.get("x-text-bin").unwrap(), "hello");
+        assert_eq!(map.get("x-raw-bin").unwrap(), "//4A");
+    }
+
+    #[test]
+    fn zero_call_options_are_defaults() {
+        let o = call_options(&GrpcRequest::default());
+        assert_eq!(o.deadline, None);
+        assert_eq!(o.max_send_size, None);
+        assert_eq!(o.max_receive_size, None);
+        assert!(!o.send_gzip && !o.accept_gzip && !o.wait_for_ready);
+
+        let req = GrpcRequest {
+            call_options: GrpcCallOptions {
+                deadline: -1,
+                max_send_size: -1,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let o = call_options(&req);
+        assert_eq!(o.deadline, None);
+        assert_eq!(o.max_send_size, None);
+    }
+
+    #[test]
+    fn set_call_options() {
+        let req = GrpcRequest {
+            call_options: GrpcCallOptions {
+                deadline: 1500,
+                send_gzip: true,
+                accept_gzip: true,
+                max_send_size: 1024,
+                max_receive_size: 2048,
+                wait_for_ready: true,
+            },
+            ..Default::default()
+        };
+        let o = call_options(&req);
+        assert_eq!(o.deadline, Some(Duration::from_millis(1500)));
+        assert_eq!(o.max_send_size, Some(1024));
+        assert_eq!(o.max_receive_size, Some(2048));
+        assert!(o.send_gzip && o.accept_gzip && o.wait_for_ready);
+    }
+}
@@ -26,6 +26,7 @@ use yaak_models::models::{
     Cookie, CookieJar, Environment, HttpRequest, HttpResponse, HttpResponseHeader, HttpUrlParameter,
 };
 use yaak_models::queries::{get_workspace, update_response_if_id, upsert_cookie_jar};
+use yaak_templates::RenderOptions;
 
 pub async fn send_http_request<R: Runtime>(
     window: &WebviewWindow<R>,
@@ -40,8 +41,18 @@ pub async fn send_http_request<R: Runtime>(
         .expect("Failed to get Workspace");
     let cb = &*window.app_handle().state::<PluginTemplateCallback>();
     let cb = cb.for_send();
-    let rendered_request =
-        render_http_request(&request, &workspace, environment.as_ref(), &cb).await;
+    let rendered_request = match render_http_request(
+        &request,
+        &workspace,
+        environment.as_ref(),
+        &cb,
+        &RenderOptions::strict(),
+    )
+    .await
+    {
+        Ok(r) => r,
+        Err(e) => return response_err(response, e.to_string(), window).await,
+    };
 
     let mut url_string = rendered_request.url;
 
@@ -10,7 +10,6 @@ use std::process::exit;
 use std::str::FromStr;
 use std::time::Duration;
 
-use base64::Engine;
 use chrono::Utc;
 use fern::colors::ColoredLevelConfig;
 use log::{debug, error, info, warn};
@@ -27,16 +26,22 @@ use tauri_plugin_shell::ShellExt;
 use tokio::sync::Mutex;
 
 use yaak_grpc::manager::{DynamicMessage, GrpcHandle};
-use yaak_grpc::{deserialize_message, serialize_message, Code, ServiceDefinition};
+use yaak_grpc::{deserialize_message, serialize_message, ChannelState, Code, ReflectResponse};
 use yaak_plugin_runtime::manager::PluginManager;
 
 use crate::analytics::{AnalyticsAction, AnalyticsResource};
 use crate::export_resources::{get_workspace_export_resources, WorkspaceExportResources};
-use crate::grpc::metadata_to_map;
+use crate::grpc::{
+    build_metadata, call_options, channel_config, metadata_to_map, set_connection_headers,
+};
 use crate::http_request::send_http_request;
+use crate::local_access::{get_local_access, set_local_access, LocalAccessSettings};
 use crate::notifications::YaakNotifier;
-use crate::render::{render_grpc_request, render_http_request, render_template};
-use crate::template_callback::PluginTemplateCallback;
+use crate::render::{
+    analyze_requests, render_http_request, render_template, render_template_provenance,
+    GrpcRenderer, VariableLayers,
+};
+use crate::template_callback::{native_template_functions, PluginTemplateCallback};
 use crate::updates::{UpdateMode, YaakUpdater};
 use crate::window_menu::app_menu;
 use yaak_models::models::{
@@ -60,15 +65,19 @@ use yaak_plugin_runtime::events::{
     CallHttpRequestActionRequest, FilterResponse, FindHttpResponsesResponse,
     GetHttpRequestActionsResponse, GetHttpRequestByIdResponse, GetTemplateFunctionsResponse,
     InternalEvent, InternalEventPayload, PluginBootResponse, RenderHttpRequestResponse,
-    SendHttpRequestResponse, ShowToastRequest, ToastVariant,
+    RenderPurpose, SendHttpRequestResponse, ShowToastRequest, ToastVariant,
 };
 use yaak_plugin_runtime::handle::PluginHandle;
-use yaak_templates::{Parser, Tokens};
+use yaak_templates::{
+    ParseError, Parser, RenderOptions, TemplateProvenance, TemplateReferences, Tokens,
+};
 
 mod analytics;
+mod env_files;
 mod export_resources;
 mod grpc;
 mod http_request;
+mod local_access;
 mod notifications;
 mod render;
 #[cfg(target_os = "macos")]
@@ -107,8 +116,16 @@ async fn cmd_metadata(app_handle: AppHandle) -> Result<AppMetaData, ()> {
 }
 
 #[tauri::command]
-async fn cmd_parse_template(template: &str) -> Result<Tokens, String> {
-    Ok(Parser::new(template).parse())
+async fn cmd_parse_template(
+    template: &str,
+    strict: Option<bool>,
+) -> Result<Tokens, Vec<ParseError>> {
+    let mut parser = Parser::new(template);
+    if strict.unwrap_or(false) {
+        parser.parse_strict()
+    } else {
+        Ok(parser.parse())
+    }
 }
 
 #[tauri::command]
@@ -122,6 +139,7 @@ async fn cmd_render_template(
     template: &str,
     workspace_id: &str,
     environment_id: Option<&str>,
+    request_id: Option<&str>,
 ) -> Result<String, String> {
     let environment = match environment_id {
         Some(id) => Some(
@@ -134,14 +152,12 @@ async fn cmd_render_template(
     let workspace = get_workspace(&window, &workspace_id)
         .await
         .map_err(|e| e.to_string())?;
-    let rendered = render_template(
-        window.app_handle(),
-        template,
-        &workspace,
-        environment.as_ref(),
-    )
-    .await;
-    Ok(rendered)
+    let layers =
+        &VariableLayers::load_for_request(&window, &workspace, environment.as_ref(), request_id)
+            .await;
+    render_template(window.app_handle(), template, layers)
+        .await
+        .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
@@ -160,13 +176,34 @@ async fn cmd_dismiss_notification(
 #[tauri::command]
 async fn cmd_grpc_reflect(
     request_id: &str,
+    environment_id: Option<&str>,
     proto_files: Vec<String>,
+    proto_includes: Vec<String>,
     window: WebviewWindow,
     grpc_handle: State<'_, Mutex<GrpcHandle>>,
-) -> Result<Vec<ServiceDefinition>, String> {
+) -> Result<ReflectResponse, String> {
+    let environment = match environment_id {
+        Some(id) => Some(
+            get_environment(&window, id)
+                .await
+                .map_err(|e| e.to_string())?,
+        ),
+        None => None,
+    };
     let req = get_grpc_request(&window, request_id)
         .await
         .map_err(|e| e.to_string())?;
+    let workspace = get_workspace(&window, &req.workspace_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    // Reflection is sent with the same metadata and auth as the request itself
+    let renderer =
+        GrpcRenderer::for_reflect(window.app_handle(), &req, &workspace, environment.as_ref())
+            .await;
+    let req = renderer
+        .render_connection(&req)
+        .await
+        .map_err(|e| e.to_string())?;
 
     let uri = safe_uri(&req.url);
 
@@ -180,6 +217,12 @@ async fn cmd_grpc_reflect(
                 .iter()
                 .map(|p| PathBuf::from_str(p).unwrap())
                 .collect(),
+            &proto_includes
+                .iter()
+                .map(|p| PathBuf::from_str(p).unwrap())
+                .collect(),
+            &channel_config(&req, &workspace),
+            &build_metadata(&req),
         )
         .await
 }
@@ -189,6 +232,7 @@ async fn cmd_grpc_go(
     request_id: &str,
     environment_id: Option<&str>,
     proto_files: Vec<String>,
+    proto_includes: Vec<String>,
     window: WebviewWindow,
     grpc_handle: State<'_, Mutex<GrpcHandle>>,
 ) -> Result<String, String> {
@@ -206,48 +250,15 @@ async fn cmd_grpc_go(
     let workspace = get_workspace(&window, &req.workspace_id)
         .await
         .map_err(|e| e.to_string())?;
-    let req =
-        render_grpc_request(window.app_handle(), &req, &workspace, environment.as_ref()).await;
-    let mut metadata = HashMap::new();
-
-    // Add the rest of metadata
-    for h in req.clone().metadata {
-        if h.name.is_empty() && h.value.is_empty() {
-            continue;
-        }
-
-        if !h.enabled {
-            continue;
-        }
-
-        metadata.insert(h.name, h.value);
-    }
-
-    if let Some(b) = &req.authentication_type {
-        let req = req.clone();
-        let empty_value = &serde_json::to_value("").unwrap();
-        let a = req.authentication;
-
-        if b == "basic" {
-            let username = a
-                .get("username")
-                .unwrap_or(empty_value)
-                .as_str()
-                .unwrap_or("");
-            let password = a
-                .get("password")
-                .unwrap_or(empty_value)
-                .as_str()
-                .unwrap_or("");
-
-            let auth = format!("{username}:{password}");
-            let encoded = base64::engine::general_purpose::STANDARD_NO_PAD.encode(auth);
-            metadata.insert("Authorization".to_string(), format!("Basic {}", encoded));
-        } else if b == "bearer" {
-            let token = a.get("token").unwrap_or(empty_value).as_str().unwrap_or("");
-            metadata.insert("Authorization".to_string(), format!("Bearer {token}"));
-        }
-    }
+    let renderer =
+        GrpcRenderer::new(window.app_handle(), &req, &workspace, environment.as_ref()).await;
+    // Events store messages as written, since rendering them can reveal secrets
+    let message_template = req.message.clone();
+    let req = renderer
+        .render_request(&req)
+        .await
+        .map_err(|e| e.to_string())?;
+    let metadata = build_metadata(&req);
 
     let conn = {
         let req = req.clone();
@@ -276,7 +287,10 @@ async fn cmd_grpc_go(
     };
 
     let (in_msg_tx, in_msg_rx) = tauri::async_runtime::channel::<DynamicMessage>(16);
-    let maybe_in_msg_tx = std::sync::Mutex::new(Some(in_msg_tx.clone()));
+    // Client messages are queued here by the listener, to be rendered in order before sending.
+    // The listener can't wait for room in the queue, so it's unbounded to never drop one.
+    let (raw_msg_tx, mut raw_msg_rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+    let maybe_raw_msg_tx = std::sync::Mutex::new(Some(raw_msg_tx));
     let (cancelled_tx, mut cancelled_rx) = tokio::sync::watch::channel(false);
 
     let uri = safe_uri(&req.url);
@@ -291,7 +305,7 @@ async fn cmd_grpc_go(
         }
     };
 
-    let start = std::time::Instant::now();
+    let connect_start = std::time::Instant::now();
     let connection = grpc_handle
         .lock()
         .await
@@ -302,6 +316,12 @@ async fn cmd_grpc_go(
                 .iter()
                 .map(|p| PathBuf::from_str(p).unwrap())
                 .collect(),
+            &proto_includes
+                .iter()
+                .map(|p| PathBuf::from_str(p).unwrap())
+                .collect(),
+            &channel_config(&req, &workspace),
+            &metadata,
         )
         .await;
 
@@ -311,7 +331,7 @@ async fn cmd_grpc_go(
             upsert_grpc_connection(
                 &window,
                 &GrpcConnection {
-                    elapsed: start.elapsed().as_millis() as i32,
+                    elapsed: connect_start.elapsed().as_millis() as i32,
                     error: Some(err.clone()),
                     ..conn.clone()
                 },
@@ -333,11 +353,54 @@ async fn cmd_grpc_go(
         Commit,
     }
 
-    let cb = {
-        let cancelled_rx = cancelled_rx.clone();
+    // Render and send client messages. The client stream ends when the listener drops the
+    // queue's sending half on commit, which drops the sending half of the stream here.
+    {
         let w = window.clone();
         let base_msg = base_msg.clone();
         let method_desc = method_desc.clone();
+        tauri::async_runtime::spawn(async move {
+            while let Some(msg) = raw_msg_rx.recv().await {
+                let rendered = match renderer.render_message(msg.as_str()).await {
+                    Ok(m) => deserialize_message(m.as_str(), method_desc.clone()),
+                    Err(e) => Err(e.to_string()),
+                };
+                let d_msg = match rendered {
+                    Ok(d) => d,
+                    Err(e) => {
+                        upsert_grpc_event(
+                            &w,
+                            &GrpcEvent {
+                                event_type: GrpcEventType::Error,
+                                content: e,
+                                ..base_msg.clone()
+                            },
+                        )
+                        .await
+                        .unwrap();
+                        continue;
+                    }
+                };
+                if in_msg_tx.send(d_msg).await.is_err() {
+                    // The call has ended, so nothing is reading the stream anymore
+                    break;
+                }
+                upsert_grpc_event(
+                    &w,
+                    &GrpcEvent {
+                        content: msg,
+                        event_type: GrpcEventType::ClientMessage,
+                        ..base_msg.clone()
+                    },
+                )
+                .await
+                .unwrap();
+            }
+        });
+    }
+
+    let cb = {
+        let cancelled_rx = cancelled_rx.clone();
 
         move |ev: tauri::Event| {
             if *cancelled_rx.borrow() {
@@ -345,11 +408,11 @@ async fn cmd_grpc_go(
                 return;
             }
 
-            let mut maybe_in_msg_tx = maybe_in_msg_tx
+            let mut maybe_raw_msg_tx = maybe_raw_msg_tx
                 .lock()
                 .expect("previous holder not to panic");
-            let in_msg_tx = if let Some(in_msg_tx) = maybe_in_msg_tx.as_ref() {
-                in_msg_tx
+            let raw_msg_tx = if let Some(raw_msg_tx) = maybe_raw_msg_tx.as_ref() {
+                raw_msg_tx
             } else {
                 // This would mean that the stream is already committed because
                 // we have already dropped the sending half
@@ -358,44 +421,12 @@ async fn cmd_grpc_go(
 
             match serde_json::from_str::<IncomingMsg>(ev.payload()) {
                 Ok(IncomingMsg::Message(msg)) => {
-                    let w = w.clone();
-                    let base_msg = base_msg.clone();
-                    let method_desc = method_desc.clone();
-                    let d_msg: DynamicMessage = match deserialize_message(msg.as_str(), method_desc)
-                    {
-                        Ok(d_msg) => d_msg,
-                        Err(e) => {
-                            tauri::async_runtime::spawn(async move {
-                                upsert_grpc_event(
-                                    &w,
-                                    &GrpcEvent {
-                                        event_type: GrpcEventType::Error,
-                                        content: e.to_string(),
-                                        ..base_msg.clone()
-                                    },
-                                )
-                                .await
-                                .unwrap();
-                            });
-                            return;
-                        }
-                    };
-                    in_msg_tx.try_send(d_msg).unwrap();
-                    tauri::async_runtime::spawn(async move {
-                        upsert_grpc_event(
-                            &w,
-                            &GrpcEvent {
-                                content: msg,
-                                event_type: GrpcEventType::ClientMessage,
-                                ..base_msg.clone()
-                            },
-                        )
-                        .await
-                        .unwrap();
-                    });
+                    if let Err(e) = raw_msg_tx.send(msg) {
+                        error!("Failed to queue gRPC message: {:?}", e);
+                    }
                 }
                 Ok(IncomingMsg::Commit) => {
-                    maybe_in_msg_tx.take();
+                    maybe_raw_msg_tx.take();
                 }
                 Ok(IncomingMsg::Cancel) => {
                     cancelled_tx.send_replace(true);
@@ -407,21 +438,32 @@ async fn cmd_grpc_go(
         }
     };
     let event_handler = window.listen_any(format!("grpc_client_msg_{}", conn.id).as_str(), cb);
+    let channel = connection.clone();
 
     let grpc_listen = {
         let w = window.clone();
         let base_event = base_msg.clone();
+        let conn_id = conn_id.clone();
+        let options = call_options(&req);
         let req = req.clone();
         let msg = if req.message.is_empty() {
             "{}".to_string()
         } else {
             req.message
         };
+        let msg_template = if message_template.is_empty() {
+            "{}".to_string()
+        } else {
+            message_template
+        };
 
         upsert_grpc_event(
             &w,
             &GrpcEvent {
-                content: format!("Connecting to {}", req.url),
+                content: match connection.state() {
+                    ChannelState::Ready => format!("Reusing connection to {}", req.url),
+                    _ => format!("Connecting to {}", req.url),
+                },
                 event_type: GrpcEventType::ConnectionStart,
                 metadata: metadata.clone(),
                 ..base_event.clone()
@@ -438,7 +480,7 @@ async fn cmd_grpc_go(
                 (true, true) => (
                     Some(
                         connection
-                            .streaming(&service, &method, in_msg_stream, metadata)
+                            .streaming(&service, &method, in_msg_stream, metadata, &options)
                             .await,
                     ),
                     None,
@@ -447,21 +489,25 @@ async fn cmd_grpc_go(
                     None,
                     Some(
                         connection
-                            .client_streaming(&service, &method, in_msg_stream, metadata)
+                            .client_streaming(&service, &method, in_msg_stream, metadata, &options)
                             .await,
                     ),
                 ),
                 (false, true) => (
                     Some(
                         connection
-                            .server_streaming(&service, &method, &msg, metadata)
+                            .server_streaming(&service, &method, &msg, metadata, &options)
                             .await,
                     ),
                     None,
                 ),
                 (false, false) => (
                     None,
-                    Some(connection.unary(&service, &method, &msg, metadata).await),
+                    Some(
+                        connection
+                            .unary(&service, &method, &msg, metadata, &options)
+                            .await,
+                    ),
                 ),
             };
 
@@ -470,7 +516,7 @@ async fn cmd_grpc_go(
                     &w,
                     &GrpcEvent {
                         event_type: GrpcEventType::ClientMessage,
-                        content: msg,
+                        content: msg_template,
                         ..base_event.clone()
                     },
                 )
@@ -480,6 +526,7 @@ async fn cmd_grpc_go(
 
             match maybe_msg {
                 Some(Ok(msg)) => {
+                    set_connection_headers(&w, &conn_id, msg.metadata()).await;
                     upsert_grpc_event(
                         &w,
                         &GrpcEvent {
@@ -487,7 +534,7 @@ async fn cmd_grpc_go(
                             content: if msg.metadata().len() == 0 {
                                 "Received response"
                             } else {
-                                "Received response with metadata"
+                                "Received response headers"
                             }
                             .to_string(),
                             event_type: GrpcEventType::Info,
@@ -524,6 +571,7 @@ async fn cmd_grpc_go(
                         &(match e.status {
                             Some(s) => GrpcEvent {
                                 error: Some(s.message().to_string()),
+                                error_details: connection.status_details(&s),
                                 status: Some(s.code() as i32),
                                 content: "Failed to connect".to_string(),
                                 metadata: metadata_to_map(s.metadata().clone()),
@@ -549,6 +597,7 @@ async fn cmd_grpc_go(
 
             let mut stream = match maybe_stream {
                 Some(Ok(stream)) => {
+                    set_connection_headers(&w, &conn_id, stream.metadata()).await;
                     upsert_grpc_event(
                         &w,
                         &GrpcEvent {
@@ -556,7 +605,7 @@ async fn cmd_grpc_go(
                             content: if stream.metadata().len() == 0 {
                                 "Received response"
                             } else {
-                                "Received response with metadata"
+                                "Received response headers"
                             }
                             .to_string(),
                             event_type: GrpcEventType::Info,
@@ -573,6 +622,7 @@ async fn cmd_grpc_go(
                         &(match e.status {
                             Some(s) => GrpcEvent {
                                 error: Some(s.message().to_string()),
+                                error_details: connection.status_details(&s),
                                 status: Some(s.code() as i32),
                                 content: "Failed to connect".to_string(),
                                 metadata: metadata_to_map(s.metadata().clone()),
@@ -636,6 +686,7 @@ async fn cmd_grpc_go(
                             &GrpcEvent {
                                 content: status.to_string(),
                                 status: Some(status.code() as i32),
+                                error_details: connection.status_details(&status),
                                 metadata: metadata_to_map(status.metadata().clone()),
                                 event_type: GrpcEventType::ConnectionEnd,
                                 ..base_event.clone()
@@ -651,6 +702,8 @@ async fn cmd_grpc_go(
 
     {
         let conn_id = conn_id.clone();
+        // Latency is measured from the call, not including loading descriptors beforehand
+        let start = std::time::Instant::now();
         tauri::async_runtime::spawn(async move {
             let w = window.clone();
             tokio::select! {
@@ -667,6 +720,7 @@ async fn cmd_grpc_go(
                         &GrpcConnection{
                             elapsed: start.elapsed().as_millis() as i32,
                             status: closed_status,
+                            channel_state: Some(channel.state().as_str().to_string()),
                             ..get_grpc_connection(&w, &conn_id).await.unwrap().clone()
                         },
                     ).await.unwrap();
@@ -834,6 +888,8 @@ async fn cmd_import_data(
 
     for mut v in resources.environments {
         v.id = maybe_gen_id(v.id.as_str(), ModelType::TypeEnvironment, &mut id_map);
+        // Attached .env files are paths on the exporter's machine, so they aren't imported
+        v.env_files = Vec::new();
         v.workspace_id = maybe_gen_id(
             v.workspace_id.as_str(),
             ModelType::TypeWorkspace,
@@ -934,14 +990,123 @@ async fn cmd_http_request_actions(
         .map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+async fn cmd_render_template_provenance(
+    window: WebviewWindow,
+    template: &str,
+    workspace_id: &str,
+    environment_id: Option<&str>,
+    request_id: Option<&str>,
+) -> Result<TemplateProvenance, String> {
+    let environment = match environment_id {
+        Some(id) => Some(
+            get_environment(&window, id)
+                .await
+                .map_err(|e| e.to_string())?,
+        ),
+        None => None,
+    };
+    let workspace = get_workspace(&window, &workspace_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let layers =
+        &VariableLayers::load_for_request(&window, &workspace, environment.as_ref(), request_id)
+            .await;
+    render_template_provenance(window.app_handle(), template, layers)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn cmd_template_references(
+    window: WebviewWindow,
+    plugin_manager: State<'_, PluginManager>,
+    workspace_id: &str,
+    environment_id: Option<&str>,
+    request_id: Option<&str>,
+) -> Result<TemplateReferences, String> {
+    let environment = match environment_id {
+        Some(id) => Some(
+            get_environment(&window, id)
+                .await
+                .map_err(|e| e.to_string())?,
+        ),
+        None => None,
+    };
+    let workspace = get_workspace(&window, workspace_id)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // Analyze every request in the workspace if one isn't specified
+    let (http_requests, grpc_requests) = match request_id {
+        Some(id) => match get_http_request(&window, id).await {
+            Ok(r) => (vec![r], Vec::new()),
+            Err(_) => (
+                Vec::new(),
+                vec![get_grpc_request(&window, id)
+                    .await
+                    .map_err(|e| e.to_string())?],
+            ),
+        },
+        None => (
+            list_http_requests(&window, workspace_id)
+                .await
+                .map_err(|e| e.to_string())?,
+            list_grpc_requests(&window, workspace_id)
+                .await
+                .map_err(|e| e.to_string())?,
+        ),
+    };
+
+    let mut functions: Vec<String> = native_template_functions()
+        .into_iter()
+        .map(|f| f.name)
+        .collect();
+    if let Ok(responses) = plugin_manager.get_template_functions().await {
+        let plugin_functions = responses.into_iter().flat_map(|r| r.functions);
+        functions.extend(plugin_functions.map(|f| f.name));
+    }
+
+    let layers = VariableLayers::load(&window, &workspace, environment.as_ref(), None).await;
+    let folders = list_folders(&window, workspace_id)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(analyze_requests(
+        &http_requests,
+        &grpc_requests,
+        &layers,
+        &folders,
+        &functions,
+    ))
+}
+
 #[tauri::command]
 async fn cmd_template_functions(
     plugin_manager: State<'_, PluginManager>,
 ) -> Result<Vec<GetTemplateFunctionsResponse>, String> {
-    plugin_manager
-        .get_template_functions()
-        .await
-        .map_err(|e| e.to_string())
+    let mut responses = match plugin_manager.get_template_functions().await {
+        Ok(r) => r,
+        Err(e) => {
+            warn!("Failed to get plugin template functions {}", e);
+            Vec::new()
+        }
+    };
+
+    // Add built-in functions that aren't overridden by a plugin
+    let plugin_fn_names: Vec<String> = responses
+        .iter()
+        .flat_map(|r| r.functions.iter().map(|f| f.name.clone()))
+        .collect();
+    responses.push(GetTemplateFunctionsResponse {
+        functions: native_template_functions()
+            .into_iter()
+            .filter(|f| !plugin_fn_names.contains(&f.name))
+            .collect(),
+        plugin_ref_id: "native".to_string(),
+    });
+
+    Ok(responses)
 }
 
 #[tauri::command]
@@ -1164,6 +1329,23 @@ async fn cmd_set_key_value(
     Ok(key_value)
 }
 
+#[tauri::command]
+async fn cmd_get_local_access(
+    workspace_id: &str,
+    w: WebviewWindow,
+) -> Result<LocalAccessSettings, String> {
+    Ok(get_local_access(&w, workspace_id).await)
+}
+
+#[tauri::command]
+async fn cmd_set_local_access(
+    workspace_id: &str,
+    settings: LocalAccessSettings,
+    w: WebviewWindow,
+) -> Result<LocalAccessSettings, String> {
+    Ok(set_local_access(&w, workspace_id, &settings).await)
+}
+
 #[tauri::command]
 async fn cmd_create_workspace(name: &str, w: WebviewWindow) -> Result<Workspace, String> {
     upsert_workspace(&w, Workspace::new(name.to_string()))
@@ -1229,6 +1411,7 @@ async fn cmd_create_environment(
     workspace_id: &str,
     name: &str,
     variables: Vec<EnvironmentVariable>,
+    environment_id: Option<&str>,
     w: WebviewWindow,
 ) -> Result<Environment, String> {
     upsert_environment(
@@ -1237,6 +1420,7 @@ async fn cmd_create_environment(
             workspace_id: workspace_id.to_string(),
             name: name.to_string(),
             variables,
+            environment_id: environment_id.map(|s| s.to_string()),
             ..Default::default()
         },
     )
@@ -1259,6 +1443,7 @@ async fn cmd_create_grpc_request(
             name: name.to_string(),
             folder_id: folder_id.map(|s| s.to_string()),
             sort_priority,
+            protocol: "grpc".to_string(),
             ..Default::default()
         },
     )
@@ -1712,7 +1897,7 @@ pub fn run() {
             app.manage(Mutex::new(yaak_notifier));
 
             // Add GRPC manager
-            let grpc_handle = GrpcHandle::new(&app.app_handle());
+            let grpc_handle = GrpcHandle::new();
             app.manage(Mutex::new(grpc_handle));
 
             // Plugin template callback
@@ -1756,6 +1941,7 @@ pub fn run() {
             cmd_get_grpc_request,
             cmd_get_http_request,
             cmd_get_key_value,
+            cmd_get_local_access,
             cmd_get_settings,
             cmd_get_workspace,
             cmd_grpc_go,
@@ -1779,12 +1965,15 @@ pub fn run() {
             cmd_plugin_info,
             cmd_reload_plugins,
             cmd_render_template,
+            cmd_render_template_provenance,
             cmd_save_response,
             cmd_send_ephemeral_request,
             cmd_send_http_request,
             cmd_set_key_value,
+            cmd_set_local_access,
             cmd_set_update_mode,
             cmd_template_functions,
+            cmd_template_references,
             cmd_template_tokens_to_string,
             cmd_track_event,
             cmd_update_cookie_jar,
@@ -2076,14 +2265,31 @@ async fn handle_plugin_event<R: Runtime>(
                 None => None,
                 Some(id) => get_environment(&w, id.as_str()).await.ok(),
             };
-            let cb = &*app_handle.state::<PluginTemplateCallback>();
-            let rendered_http_request =
-                render_http_request(&req.http_request, &workspace, environment.as_ref(), cb).await;
-            Some(InternalEventPayload::RenderHttpRequestResponse(
-                RenderHttpRequestResponse {
-                    http_request: rendered_http_request,
+            // Secrets are only revealed to plugins when rendering to send
+            let cb = app_handle.state::<PluginTemplateCallback>();
+            let cb = &match req.purpose {
+                RenderPurpose::Send => cb.for_send(),
+                RenderPurpose::Preview => cb.inner().clone(),
+            };
+            let opt = &RenderOptions::default();
+            let rendered =
+                render_http_request(&req.http_request, &workspace, environment.as_ref(), cb, opt)
+                    .await;
+            // Always reply, since the plugin is waiting on it
+            let response = match rendered {
+                Ok(http_request) => RenderHttpRequestResponse {
+                    http_request,
+                    error: None,
                 },
-            ))
+                Err(e) => {
+                    warn!("Failed to render request for plugin: {}", e);
+                    RenderHttpRequestResponse {
+                        http_request: req.http_request,
+                        error: Some(e.to_string()),
+                    }
+                }
+            };
+            Some(InternalEventPayload::RenderHttpRequestResponse(response))
         }
         InternalEventPayload::ReloadResponse(_) => {
             let w = get_focused_window_no_lock(app_handle).expect("No focused window");
@@ -0,0 +1,333 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tauri::{Manager, Runtime, WebviewWindow};
+use yaak_models::queries::{get_key_value_raw, set_key_value_raw};
+use yaak_templates::native::{arg_i64, arg_str, required_str};
+
+const KV_NAMESPACE: &str = "local_access";
+const DEFAULT_TIMEOUT_SECONDS: i64 = 10;
+const DEFAULT_TTL_SECONDS: i64 = 60;
+
+/// Template functions that read local files or run local commands. They only work with
+/// what the workspace's allowlist permits.
+pub const LOCAL_FUNCTIONS: &[(&str, &[&str])] = &[
+    ("file", &["path", "timeout", "ttl"]),
+    ("cmd", &["program", "args", "timeout", "ttl"]),
+];
+
+/// Which local files and commands a workspace's templates may use. This is stored in
+/// local key-values instead of on the workspace, so it isn't shared along with it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct LocalAccessSettings {
+    /// Commands `cmd()` may run. An entry with arguments, like `gcloud auth print-access-token`,
+    /// only allows exactly those arguments. A bare program allows it with any arguments,
+    /// which for shells or CLIs like `gcloud` is as good as allowing any command.
+    pub commands: Vec<String>,
+    /// Files, or directories of files, that `file()` may read and `.env` files may be
+    /// attached from
+    pub paths: Vec<String>,
+    /// Process environment variables templates may read as `env.NAME`
+    pub env_vars: Vec<String>,
+    /// Also run the functions when rendering previews, instead of only when sending
+    pub allow_in_preview: bool,
+}
+
+pub async fn get_local_access<R: Runtime>(
+    mgr: &impl Manager<R>,
+    workspace_id: &str,
+) -> LocalAccessSettings {
+    match get_key_value_raw(mgr, KV_NAMESPACE, workspace_id).await {
+        Some(kv) => serde_json::from_str(&kv.value).unwrap_or_default(),
+        None => LocalAccessSettings::default(),
+    }
+}
+
+pub async fn set_local_access<R: Runtime>(
+    w: &WebviewWindow<R>,
+    workspace_id: &str,
+    settings: &LocalAccessSettings,
+) -> LocalAccessSettings {
+    let value = serde_json::to_string(settings).unwrap_or_default();
+    let (kv, _created) = set_key_value_raw(w, KV_NAMESPACE, workspace_id, &value).await;
+    serde_json::from_str(&kv.value).unwrap_or_default()
+}
+
+pub fn is_local_function(fn_name: &str) -> bool {
+    LOCAL_FUNCTIONS.iter().any(|(name, _)| *name == fn_name)
+}
+
+// Output of recent calls, by function and args, along with when it expires
+type OutputCache = HashMap<String, (Instant, String)>;
+
+static CACHE: OnceLock<Mutex<OutputCache>> = OnceLock::new();
+
+pub async fn call_local_function(
+    fn_name: &str,
+    args: &HashMap<String, Value>,
+    settings: &LocalAccessSettings,
+) -> Result<String, String> {
+    // Check the allowlist before the cache, so removing something takes effect immediately.
+    // Files are read from the canonical path that was checked, not the one given.
+    let path = match fn_name {
+        "file" => Some(check_path_allowed(&required_str(args, "path")?, settings)?),
+        "cmd" => {
+            check_command_allowed(
+                &required_str(args, "program")?,
+                &command_args(args),
+                settings,
+            )?;
+            None
+        }
+        _ => return Err(format!("Template function {fn_name} not found")),
+    };
+
+    let cache = CACHE.get_or_init(|| Mutex::new(HashMap::new()));
+    let key = format!(
+        "{fn_name}({})",
+        Value::Object(args.clone().into_iter().collect())
+    );
+    if let Some((expires, output)) = cache.lock().unwrap().get(&key) {
+        if *expires > Instant::now() {
+            return Ok(output.to_owned());
+        }
+    }
+
+    let timeout =
+        Duration::from_secs(arg_i64(args, "timeout", DEFAULT_TIMEOUT_SECONDS)?.max(0) as u64);
+    let output = match path {
+        Some(path) => read_file(&path, timeout).await?,
+        None => run_command(&required_str(args, "program")?, command_args(args), timeout).await?,
+    };
+
+    let ttl = arg_i64(args, "ttl", DEFAULT_TTL_SECONDS)?;
+    if ttl > 0 {
+        let expires = Instant::now() + Duration::from_secs(ttl as u64);
+        cache.lock().unwrap().insert(key, (expires, output.clone()));
+    }
+
+    Ok(output)
+}
+
+fn check_command_allowed(
+    program: &str,
+    args: &[String],
+    settings: &LocalAccessSettings,
+) -> Result<(), String> {
+    let allowed = settings.commands.iter().any(|c| {
+        let mut allowed = c.split_whitespace();
+        match allowed.next() {
+            Some(p) if p == program => {
+                let allowed_args = allowed.collect::<Vec<&str>>();
+                allowed_args.is_empty() || allowed_args == args
+            }
+            _ => false,
+        }
+    });
+    match allowed {
+        true => Ok(()),
+        false => {
+            let command = std::iter::once(program)
+                .chain(args.iter().map(|a| a.as_str()))
+                .collect::<Vec<&str>>()
+                .join(" ");
+            Err(format!(
+                "Command {command} is not allowed in this workspace"
+            ))
+        }
+    }
+}
+
+/// Check the path against the allowlist, returning its canonical form
+pub fn check_path_allowed(path: &str, settings: &LocalAccessSettings) -> Result<PathBuf, String> {
+    // Compare canonical paths, so `..` and symlinks can't be used to escape the allowlist
+    let not_allowed = || format!("File {path} is not allowed in this workspace");
+    let path = canonicalize(path).ok_or_else(not_allowed)?;
+    let allowed = settings
+        .paths
+        .iter()
+        .filter_map(|p| canonicalize(p))
+        .any(|p| path.starts_with(p));
+    match allowed {
+        true => Ok(path),
+        false => Err(not_allowed()),
+    }
+}
+
+fn canonicalize(path: &str) -> Option<PathBuf> {
+    Path::new(path).canonicalize().ok()
+}
+
+/// Arguments can be an array, or a string split on whitespace
+fn command_args(args: &HashMap<String, Value>) -> Vec<String> {
+    match args.get("args") {
+        Some(Value::Array(a)) => a
+            .iter()
+            .map(|v| match v {
+                Value::String(s) => s.to_string(),
+                v => v.to_string(),
+            })
+            .collect(),
+        _ => arg_str(args, "args")
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(|s| s.to_string())
+            .collect(),
+    }
+}
+
+async fn read_file(path: &Path, timeout: Duration) -> Result<String, String> {
+    tokio::time::timeout(timeout, tokio::fs::read_to_string(path))
+        .await
+        .map_err(|_| format!("Reading {} timed out", path.display()))?
+        .map_err(|e| format!("Failed to read {}: {e}", path.display()))
+}
+
+async fn run_command(
+    program: &str,
+    args: Vec<String>,
+    timeout: Duration,
+) -> Result<String, String> {
+    let output = tokio::process::Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output();
+    let output = tokio::time::timeout(timeout, output)
+        .await
+        .map_err(|_| format!("{program} timed out after {}s", timeout.as_secs()))?
+        .map_err(|e| format!("Failed to run {program}: {e}"))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!(
+            "{program} failed with {}: {}",
+            output.status,
+            stderr.trim()
+        ));
+    }
+
+    // Commands like `gcloud auth print-access-token` end their output with a newline
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(stdout.trim_end_matches(['\n', '\r']).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::local_access::{
+        call_local_function, check_command_allowed, check_path_allowed, LocalAccessSettings,
+    };
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    fn args(v: Value) -> HashMap<String, Value> {
+        serde_json::from_value(v).unwrap()
+    }
+
+    /// A directory of its own in the temp dir, so test runs don't share files
+    fn temp_dir(name: &str) -> PathBuf {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("{name}_{}_{nanos}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn command_allowlist() {
+        let settings = LocalAccessSettings {
+            commands: vec![
+                "echo".to_string(),
+                "gcloud auth print-access-token".to_string(),
+            ],
+            ..Default::default()
+        };
+        let args = |a: &[&str]| a.iter().map(|a| a.to_string()).collect::<Vec<String>>();
+
+        // Bare programs allow any arguments
+        assert!(check_command_allowed("echo", &args(&["anything"]), &settings).is_ok());
+
+        // Full commands only allow exactly their arguments
+        let token = args(&["auth", "print-access-token"]);
+        assert!(check_command_allowed("gcloud", &token, &settings).is_ok());
+        assert_eq!(
+            check_command_allowed("gcloud", &args(&["projects", "delete", "x"]), &settings),
+            Err("Command gcloud projects delete x is not allowed in this workspace".to_string())
+        );
+        assert!(check_command_allowed("gcloud", &[], &settings).is_err());
+        assert_eq!(
+            check_command_allowed("ls", &[], &settings),
+            Err("Command ls is not allowed in this workspace".to_string())
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn cmd_allowlist() {
+        let settings = LocalAccessSettings {
+            commands: vec!["echo".to_string()],
+            ..Default::default()
+        };
+        let result = call_local_function(
+            "cmd",
+            &args(json!({"program": "echo", "args": ["hello", "world"]})),
+            &settings,
+        )
+        .await;
+        assert_eq!(result, Ok("hello world".to_string()));
+
+        let result = call_local_function("cmd", &args(json!({"program": "ls"})), &settings).await;
+        assert_eq!(
+            result,
+            Err("Command ls is not allowed in this workspace".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn file_allowlist() {
+        let root = temp_dir("yaak_local_access_test");
+        let dir = root.join("allowed");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("key.txt");
+        std::fs::write(&path, "secret-key\n").unwrap();
+
+        let settings = LocalAccessSettings {
+            paths: vec![dir.to_string_lossy().to_string()],
+            ..Default::default()
+        };
+        let result = call_local_function(
+            "file",
+            &args(json!({"path": path.to_string_lossy(), "ttl": 0})),
+            &settings,
+        )
+        .await;
+        assert_eq!(result, Ok("secret-key\n".to_string()));
+
+        let outside = dir.join("..").join("other.txt");
+        std::fs::write(&outside, "nope").unwrap();
+        let result = call_local_function(
+            "file",
+            &args(json!({"path": outside.to_string_lossy()})),
+            &settings,
+        )
+        .await;
+        assert!(result.is_err());
+
+        // The canonical path is what gets read
+        let indirect = dir.join("..").join("allowed").join("key.txt");
+        assert_eq!(
+            check_path_allowed(&indirect.to_string_lossy(), &settings),
+            Ok(path.canonicalize().unwrap())
+        );
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
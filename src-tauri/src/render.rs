@@ -1,54 +1,406 @@
+use crate::env_files::{load_env_file, EnvFile};
+use crate::local_access::{check_path_allowed, get_local_access, is_local_function};
 use crate::template_callback::PluginTemplateCallback;
+use log::warn;
 use serde_json::{json, Map, Value};
 use std::collections::HashMap;
 use tauri::{AppHandle, Manager, Runtime};
 use yaak_models::models::{
-    Environment, EnvironmentVariable, GrpcMetadataEntry, GrpcRequest, HttpRequest,
-    HttpRequestHeader, HttpUrlParameter, Workspace,
+    Environment, EnvironmentVariable, EnvironmentVariableType, Folder, GrpcMetadataEntry,
+    GrpcRequest, HttpRequest, HttpRequestHeader, HttpUrlParameter, Workspace,
 };
-use yaak_templates::{parse_and_render, TemplateCallback};
+use yaak_models::queries::{get_environment, get_folder, get_grpc_request, get_http_request};
+use yaak_models::secrets::{decrypt_secret, SECRET_MASK};
+use yaak_plugin_runtime::events::GetTemplateFunctionsResponse;
+use yaak_plugin_runtime::manager::PluginManager;
+use yaak_templates::error::Result;
+use yaak_templates::native::find_native_function;
+use yaak_templates::{
+    analyze_references, escape_template, parse_and_render, parse_and_render_tags,
+    referenced_variables, resolve_variables, Parser, RenderOptions, TagProvenance, TagSource,
+    TemplateCallback, TemplateProvenance, TemplateReferences, PROCESS_ENV_PREFIX,
+};
+
+/// Everything variables are resolved from, with later layers taking precedence: allowed
+/// process environment → workspace → parent environments → environment → folders → request.
+/// Each environment's `.env` files sit just below the environment's own variables.
+pub struct VariableLayers {
+    pub workspace: Workspace,
+    /// The environment and its parents, from the root parent down
+    pub environments: Vec<Environment>,
+    /// The `.env` files attached to each environment, by environment ID
+    pub env_files: HashMap<String, Vec<EnvFile>>,
+    /// The request's folder and its parents, from the top-level folder down
+    pub folders: Vec<Folder>,
+    /// The request's own variables
+    pub request_variables: Vec<EnvironmentVariable>,
+    /// Values of the process environment variables the workspace allows, by name without
+    /// the `env.` prefix
+    pub process_env: HashMap<String, String>,
+}
+
+impl VariableLayers {
+    pub async fn load<R: Runtime>(
+        mgr: &impl Manager<R>,
+        w: &Workspace,
+        e: Option<&Environment>,
+        folder_id: Option<&str>,
+    ) -> VariableLayers {
+        let mut environments: Vec<Environment> = Vec::new();
+        let mut next = e.cloned();
+        while let Some(env) = next {
+            if environments.iter().any(|p| p.id == env.id) {
+                warn!("Environment {} extends itself", env.id);
+                break;
+            }
+            next = match env.environment_id.as_deref() {
+                Some(id) => get_environment(mgr, id).await.ok(),
+                None => None,
+            };
+            environments.push(env);
+        }
+        environments.reverse();
+
+        // Both env files and the process environment are local, so the workspace has to
+        // allow them first
+        let local_access = get_local_access(mgr, &w.id).await;
+        let mut env_files = HashMap::new();
+        for e in &environments {
+            let mut files = Vec::new();
+            for p in &e.env_files {
+                match check_path_allowed(p, &local_access) {
+                    Ok(canonical) => files.push(load_env_file(p, &canonical).await),
+                    Err(e) => warn!("Skipping env file: {e}"),
+                }
+            }
+            env_files.insert(e.id.to_string(), files);
+        }
+        let process_env = local_access
+            .env_vars
+            .iter()
+            .filter_map(|n| Some((n.to_string(), std::env::var(n).ok()?)))
+            .collect();
+
+        let mut folders: Vec<Folder> = Vec::new();
+        let mut next = match folder_id {
+            Some(id) => get_folder(mgr, id).await.ok(),
+            None => None,
+        };
+        while let Some(folder) = next {
+            if folders.iter().any(|p| p.id == folder.id) {
+                warn!("Folder {} is inside itself", folder.id);
+                break;
+            }
+            next = match folder.folder_id.as_deref() {
+                Some(id) => get_folder(mgr, id).await.ok(),
+                None => None,
+            };
+            folders.push(folder);
+        }
+        folders.reverse();
+
+        VariableLayers {
+            workspace: w.to_owned(),
+            environments,
+            env_files,
+            folders,
+            request_variables: Vec::new(),
+            process_env,
+        }
+    }
+
+    /// Load the layers a request sees, which include its folders and own variables
+    pub async fn load_for_request<R: Runtime>(
+        mgr: &impl Manager<R>,
+        w: &Workspace,
+        e: Option<&Environment>,
+        request_id: Option<&str>,
+    ) -> VariableLayers {
+        let (folder_id, variables) = match request_id {
+            None => (None, Vec::new()),
+            Some(id) => match get_http_request(mgr, id).await {
+                Ok(r) => (r.folder_id, r.variables),
+                Err(_) => match get_grpc_request(mgr, id).await {
+                    Ok(r) => (r.folder_id, Vec::new()),
+                    Err(_) => (None, Vec::new()),
+                },
+            },
+        };
+        VariableLayers::load(mgr, w, e, folder_id.as_deref())
+            .await
+            .with_request_variables(&variables)
+    }
+
+    /// Add the variables of the request being rendered, as the last layer
+    pub fn with_request_variables(mut self, variables: &[EnvironmentVariable]) -> Self {
+        self.request_variables = variables.to_vec();
+        self
+    }
+}
 
 pub async fn render_template<R: Runtime>(
     app_handle: &AppHandle<R>,
     template: &str,
-    w: &Workspace,
-    e: Option<&Environment>,
-) -> String {
-    let cb = &*app_handle.state::<PluginTemplateCallback>();
-    let vars = &variables_from_environment(w, e, cb).await;
-    render(template, vars, cb).await
+    layers: &VariableLayers,
+) -> Result<String> {
+    let cb = &app_handle
+        .state::<PluginTemplateCallback>()
+        .for_workspace(&layers.workspace.id);
+    let opt = &RenderOptions::default();
+    let vars = &variables_from_layers(layers, &[template], cb, opt, true).await?;
+    render(template, vars, cb, opt).await
 }
 
-pub async fn render_grpc_request<R: Runtime>(
+/// Render the template, along with where each tag's variables and functions came from
+pub async fn render_template_provenance<R: Runtime>(
     app_handle: &AppHandle<R>,
-    r: &GrpcRequest,
-    w: &Workspace,
-    e: Option<&Environment>,
-) -> GrpcRequest {
-    let cb = &*app_handle.state::<PluginTemplateCallback>();
-    let vars = &variables_from_environment(w, e, cb).await;
+    template: &str,
+    layers: &VariableLayers,
+) -> Result<TemplateProvenance> {
+    let cb = &app_handle
+        .state::<PluginTemplateCallback>()
+        .for_workspace(&layers.workspace.id);
+    let opt = &RenderOptions::default();
+    let vars = &variables_from_layers(layers, &[template], cb, opt, true).await?;
+    let (value, rendered_tags) = parse_and_render_tags(template, vars, cb, opt).await?;
 
-    let mut metadata = Vec::new();
-    for p in r.metadata.clone() {
-        metadata.push(GrpcMetadataEntry {
-            enabled: p.enabled,
-            name: render(p.name.as_str(), vars, cb).await,
-            value: render(p.value.as_str(), vars, cb).await,
-        })
+    let plugin_manager = &*app_handle.state::<PluginManager>();
+    let plugin_functions = plugin_manager
+        .get_template_functions()
+        .await
+        .unwrap_or_default();
+
+    let raw_vars = &layer_variables(layers, true);
+    let mut tags = Vec::new();
+    for t in rendered_tags {
+        let mut resolution_paths = Vec::new();
+        for name in &t.variables {
+            let mut paths = Vec::new();
+            variable_paths(name, raw_vars, &mut Vec::new(), &mut paths);
+            resolution_paths.extend(paths.into_iter().map(|path| {
+                path.into_iter()
+                    .map(|n| variable_source(n, layers))
+                    .collect::<Vec<TagSource>>()
+            }));
+        }
+
+        let mut sources = Vec::new();
+        for name in t.variables {
+            sources.push(variable_source(name, layers));
+        }
+        for name in t.functions {
+            sources.push(function_source(name, &plugin_functions, plugin_manager).await);
+        }
+        tags.push(TagProvenance {
+            tag: t.tag,
+            position: t.position,
+            value: t.value,
+            sources,
+            resolution_paths,
+        });
     }
 
-    let mut authentication = HashMap::new();
-    for (k, v) in r.authentication.clone() {
-        authentication.insert(k, render_json_value(v, vars, cb).await);
+    Ok(TemplateProvenance { value, tags })
+}
+
+/// Follow the variables each variable references, adding a path for every chain of them.
+/// A variable that closes a cycle ends the path.
+fn variable_paths(
+    name: &str,
+    vars: &HashMap<String, String>,
+    path: &mut Vec<String>,
+    paths: &mut Vec<Vec<String>>,
+) {
+    let cycle = path.iter().any(|n| n == name);
+    path.push(name.to_string());
+
+    let refs = match vars.get(name) {
+        Some(value) if !cycle => referenced_variables(&Parser::new(value).parse()),
+        _ => Vec::new(),
+    };
+    if refs.is_empty() {
+        paths.push(path.clone());
+    }
+    for r in refs {
+        variable_paths(&r, vars, path, paths);
     }
 
-    let url = render(r.url.as_str(), vars, cb).await;
+    path.pop();
+}
 
-    GrpcRequest {
-        url,
-        metadata,
-        authentication,
-        ..r.to_owned()
+/// Check layers from the highest precedence down, the same as variables_from_layers
+fn variable_source(name: String, layers: &VariableLayers) -> TagSource {
+    let defines = |variables: &[EnvironmentVariable]| {
+        variables
+            .iter()
+            .any(|v| v.enabled && !v.value.is_empty() && v.name == name)
+    };
+
+    if defines(&layers.request_variables) {
+        return TagSource::Request { variable: name };
+    }
+
+    if let Some(f) = layers.folders.iter().rev().find(|f| defines(&f.variables)) {
+        return TagSource::Folder {
+            folder_id: f.id.to_string(),
+            folder_name: f.name.to_string(),
+            variable: name,
+        };
+    }
+
+    for e in layers.environments.iter().rev() {
+        if defines(&e.variables) {
+            return TagSource::Environment {
+                environment_id: e.id.to_string(),
+                environment_name: e.name.to_string(),
+                variable: name,
+            };
+        }
+        let mut files = layers.env_files.get(&e.id).into_iter().flatten().rev();
+        if let Some(f) = files.find(|f| defines(&f.variables)) {
+            return TagSource::EnvFile {
+                environment_id: e.id.to_string(),
+                environment_name: e.name.to_string(),
+                path: f.path.to_string(),
+                variable: name,
+            };
+        }
+    }
+
+    let w = &layers.workspace;
+    if defines(&w.variables) {
+        return TagSource::Workspace {
+            workspace_id: w.id.to_string(),
+            workspace_name: w.name.to_string(),
+            variable: name,
+        };
+    }
+
+    match name.strip_prefix(PROCESS_ENV_PREFIX) {
+        Some(n) if layers.process_env.contains_key(n) => TagSource::ProcessEnv { variable: name },
+        _ => TagSource::Unknown { name },
+    }
+}
+
+/// Plugin functions override native ones, the same as PluginTemplateCallback
+async fn function_source(
+    name: String,
+    plugin_functions: &[GetTemplateFunctionsResponse],
+    plugin_manager: &PluginManager,
+) -> TagSource {
+    let plugin_ref_id = plugin_functions
+        .iter()
+        .find(|r| r.functions.iter().any(|f| f.name == name))
+        .map(|r| r.plugin_ref_id.as_str());
+
+    if let Some(ref_id) = plugin_ref_id {
+        let plugin = match plugin_manager.get_plugin(ref_id).await {
+            Ok(p) => p.name().await,
+            Err(_) => ref_id.to_string(),
+        };
+        return TagSource::Plugin {
+            plugin,
+            function: name,
+        };
+    }
+
+    match find_native_function(name.as_str()).is_some() || is_local_function(name.as_str()) {
+        true => TagSource::Native { function: name },
+        false => TagSource::Unknown { name },
+    }
+}
+
+/// Renders a gRPC request and the messages sent on it
+pub struct GrpcRenderer {
+    cb: PluginTemplateCallback,
+    layers: VariableLayers,
+}
+
+impl GrpcRenderer {
+    pub async fn new<R: Runtime>(
+        app_handle: &AppHandle<R>,
+        r: &GrpcRequest,
+        w: &Workspace,
+        e: Option<&Environment>,
+    ) -> GrpcRenderer {
+        // gRPC requests are only rendered to be sent, so secrets aren't masked
+        let cb = app_handle.state::<PluginTemplateCallback>().for_send();
+        GrpcRenderer::with_callback(app_handle, cb, r, w, e).await
+    }
+
+    /// Reflection runs while the request is being edited, so functions run as they would
+    /// for a preview. Secrets are still revealed, since the server may need them to
+    /// authenticate the reflection call.
+    pub async fn for_reflect<R: Runtime>(
+        app_handle: &AppHandle<R>,
+        r: &GrpcRequest,
+        w: &Workspace,
+        e: Option<&Environment>,
+    ) -> GrpcRenderer {
+        let cb = app_handle.state::<PluginTemplateCallback>().inner().clone();
+        GrpcRenderer::with_callback(app_handle, cb, r, w, e).await
+    }
+
+    async fn with_callback<R: Runtime>(
+        app_handle: &AppHandle<R>,
+        cb: PluginTemplateCallback,
+        r: &GrpcRequest,
+        w: &Workspace,
+        e: Option<&Environment>,
+    ) -> GrpcRenderer {
+        let cb = cb.for_workspace(&w.id);
+        let layers = VariableLayers::load(app_handle, w, e, r.folder_id.as_deref()).await;
+        GrpcRenderer { cb, layers }
+    }
+
+    pub async fn render_request(&self, r: &GrpcRequest) -> Result<GrpcRequest> {
+        let r = self.render_connection(r).await?;
+        let message = self.render_message(&r.message).await?;
+        Ok(GrpcRequest { message, ..r })
+    }
+
+    /// Render what's needed to connect, which is the URL, metadata, and authentication,
+    /// leaving the message as is
+    pub async fn render_connection(&self, r: &GrpcRequest) -> Result<GrpcRequest> {
+        let cb = &self.cb;
+        let opt = &RenderOptions::default();
+        let templates = grpc_connection_templates(r);
+        let templates = templates
+            .iter()
+            .map(|(_, t)| t.as_str())
+            .collect::<Vec<&str>>();
+        let vars = &variables_from_layers(&self.layers, &templates, cb, opt, false).await?;
+
+        let mut metadata = Vec::new();
+        for p in r.metadata.clone() {
+            metadata.push(GrpcMetadataEntry {
+                enabled: p.enabled,
+                name: render(p.name.as_str(), vars, cb, opt).await?,
+                value: render(p.value.as_str(), vars, cb, opt).await?,
+            })
+        }
+
+        let mut authentication = HashMap::new();
+        for (k, v) in r.authentication.clone() {
+            authentication.insert(k, render_json_value(v, vars, cb, opt).await?);
+        }
+
+        let url = render(r.url.as_str(), vars, cb, opt).await?;
+
+        Ok(GrpcRequest {
+            url,
+            metadata,
+            authentication,
+            ..r.to_owned()
+        })
+    }
+
+    /// Render a JSON message streamed from the client
+    pub async fn render_message(&self, message: &str) -> Result<String> {
+        let cb = &self.cb;
+        let opt = &RenderOptions::default();
+        let vars = &variables_from_layers(&self.layers, &[message], cb, opt, false).await?;
+        render(message, &json_variables(vars, &self.layers), cb, opt).await
     }
 }
 
@@ -57,15 +409,25 @@ pub async fn render_http_request(
     w: &Workspace,
     e: Option<&Environment>,
     cb: &PluginTemplateCallback,
-) -> HttpRequest {
-    let vars = &variables_from_environment(w, e, cb).await;
+    opt: &RenderOptions,
+) -> Result<HttpRequest> {
+    let cb = &cb.for_workspace(&w.id);
+    let layers = &VariableLayers::load(cb.app_handle(), w, e, r.folder_id.as_deref())
+        .await
+        .with_request_variables(&r.variables);
+    let templates = http_request_templates(r);
+    let templates = templates
+        .iter()
+        .map(|(_, t)| t.as_str())
+        .collect::<Vec<&str>>();
+    let vars = &variables_from_layers(layers, &templates, cb, opt, cb.masks_secrets()).await?;
 
     let mut url_parameters = Vec::new();
     for p in r.url_parameters.clone() {
         url_parameters.push(HttpUrlParameter {
             enabled: p.enabled,
-            name: render(p.name.as_str(), vars, cb).await,
-            value: render(p.value.as_str(), vars, cb).await,
+            name: render(p.name.as_str(), vars, cb, opt).await?,
+            value: render(p.value.as_str(), vars, cb, opt).await?,
         })
     }
 
@@ -73,80 +435,280 @@ pub async fn render_http_request(
     for p in r.headers.clone() {
         headers.push(HttpRequestHeader {
             enabled: p.enabled,
-            name: render(p.name.as_str(), vars, cb).await,
-            value: render(p.value.as_str(), vars, cb).await,
+            name: render(p.name.as_str(), vars, cb, opt).await?,
+            value: render(p.value.as_str(), vars, cb, opt).await?,
         })
     }
 
+    // Typed variables are formatted as JSON when inlined into a JSON body
+    let body_vars = &match r.body_type.as_deref() {
+        Some("application/json") => json_variables(vars, layers),
+        _ => vars.to_owned(),
+    };
     let mut body = HashMap::new();
     for (k, v) in r.body.clone() {
-        body.insert(k, render_json_value(v, vars, cb).await);
+        body.insert(k, render_json_value(v, body_vars, cb, opt).await?);
     }
 
     let mut authentication = HashMap::new();
     for (k, v) in r.authentication.clone() {
-        authentication.insert(k, render_json_value(v, vars, cb).await);
+        authentication.insert(k, render_json_value(v, vars, cb, opt).await?);
     }
 
-    let url = render(r.url.clone().as_str(), vars, cb).await;
-    HttpRequest {
+    let url = render(r.url.clone().as_str(), vars, cb, opt).await?;
+    Ok(HttpRequest {
         url,
         url_parameters,
         headers,
         body,
         authentication,
         ..r.to_owned()
+    })
+}
+
+/// Find the variables and functions the HTTP and gRPC requests reference, and which
+/// variables none of them use. Variables of the requests, and of the folders they are in,
+/// are included.
+pub fn analyze_requests(
+    http_requests: &[HttpRequest],
+    grpc_requests: &[GrpcRequest],
+    layers: &VariableLayers,
+    folders: &[Folder],
+    functions: &[String],
+) -> TemplateReferences {
+    // Each request's name, folder, own variables, and templates
+    let http = http_requests.iter().map(|r| {
+        let templates = http_request_templates(r);
+        (&r.name, &r.folder_id, r.variables.as_slice(), templates)
+    });
+    let grpc = grpc_requests.iter().map(|r| {
+        let templates = grpc_request_templates(r);
+        (&r.name, &r.folder_id, [].as_slice(), templates)
+    });
+    let requests = http.chain(grpc).collect::<Vec<_>>();
+
+    let mut variables: Vec<&EnvironmentVariable> = layers.workspace.variables.iter().collect();
+    for e in &layers.environments {
+        for f in layers.env_files.get(&e.id).into_iter().flatten() {
+            variables.extend(f.variables.iter());
+        }
+        variables.extend(e.variables.iter());
+    }
+    let mut seen_folder_ids = Vec::new();
+    for (_, folder_id, request_variables, _) in &requests {
+        let mut folder_id = folder_id.as_ref();
+        while let Some(f) = folder_id.and_then(|id| folders.iter().find(|f| &f.id == id)) {
+            if seen_folder_ids.contains(&&f.id) {
+                break;
+            }
+            seen_folder_ids.push(&f.id);
+            variables.extend(f.variables.iter());
+            folder_id = f.folder_id.as_ref();
+        }
+        variables.extend(request_variables.iter());
+    }
+
+    let mut vars = process_env_variables(layers);
+    for v in variables.into_iter().filter(|v| v.enabled) {
+        vars.insert(v.name.to_string(), v.value.to_string());
+    }
+
+    let mut templates = Vec::new();
+    for (name, _, _, request_templates) in &requests {
+        for (location, template) in request_templates {
+            // Include the request name when analyzing more than one
+            let location = match requests.len() {
+                1 => location.to_string(),
+                _ => format!("{} / {}", name, location),
+            };
+            templates.push((location, template.to_string()));
+        }
     }
+
+    analyze_references(&templates, &vars, functions)
+}
+
+/// All templated fields of the request, along with where they are
+fn http_request_templates(r: &HttpRequest) -> Vec<(String, String)> {
+    let mut templates = vec![("url".to_string(), r.url.to_string())];
+    for p in &r.url_parameters {
+        let location = format!("urlParameters.{}", p.name);
+        templates.push((location.clone(), p.name.to_string()));
+        templates.push((location, p.value.to_string()));
+    }
+    for h in &r.headers {
+        let location = format!("headers.{}", h.name);
+        templates.push((location.clone(), h.name.to_string()));
+        templates.push((location, h.value.to_string()));
+    }
+    for (k, v) in &r.body {
+        json_value_templates(format!("body.{k}"), v, &mut templates);
+    }
+    for (k, v) in &r.authentication {
+        json_value_templates(format!("authentication.{k}"), v, &mut templates);
+    }
+    templates
 }
 
-pub async fn recursively_render_variables<'s, T: TemplateCallback>(
-    m: &HashMap<String, String>,
-    render_count: usize,
+/// All templated fields of the gRPC request, along with where they are
+fn grpc_request_templates(r: &GrpcRequest) -> Vec<(String, String)> {
+    let mut templates = grpc_connection_templates(r);
+    templates.push(("message".to_string(), r.message.to_string()));
+    templates
+}
+
+fn grpc_connection_templates(r: &GrpcRequest) -> Vec<(String, String)> {
+    let mut templates = vec![("url".to_string(), r.url.to_string())];
+    for m in &r.metadata {
+        let location = format!("metadata.{}", m.name);
+        templates.push((location.clone(), m.name.to_string()));
+        templates.push((location, m.value.to_string()));
+    }
+    for (k, v) in &r.authentication {
+        json_value_templates(format!("authentication.{k}"), v, &mut templates);
+    }
+    templates
+}
+
+fn json_value_templates(location: String, v: &Value, templates: &mut Vec<(String, String)>) {
+    match v {
+        Value::String(s) => templates.push((location, s.to_string())),
+        Value::Array(a) => {
+            for (i, v) in a.iter().enumerate() {
+                json_value_templates(format!("{location}[{i}]"), v, templates);
+            }
+        }
+        Value::Object(o) => {
+            for (k, v) in o {
+                let location = format!("{location}.{k}");
+                templates.push((location.clone(), k.to_string()));
+                json_value_templates(location, v, templates);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The variables the templates use, rendered along with the ones they depend on
+pub async fn variables_from_layers<T: TemplateCallback>(
+    layers: &VariableLayers,
+    templates: &[&str],
     cb: &T,
-) -> HashMap<String, String> {
-    let mut did_render = false;
-    let mut new_map = m.clone();
-    for (k, v) in m.clone() {
-        let rendered = Box::pin(render(v.as_str(), m, cb)).await;
-        if rendered != v {
-            did_render = true
+    opt: &RenderOptions,
+    mask_secrets: bool,
+) -> Result<HashMap<String, String>> {
+    let variables = layer_variables(layers, mask_secrets);
+
+    let mut used = Vec::new();
+    for t in templates {
+        used.extend(referenced_variables(&Parser::new(t).parse()));
+    }
+
+    resolve_variables(&variables, &used, cb, opt).await
+}
+
+/// The variables of every layer, before they're rendered
+fn layer_variables(layers: &VariableLayers, mask_secrets: bool) -> HashMap<String, String> {
+    let mut variables = process_env_variables(layers);
+    variables = add_variable_to_map(variables, &layers.workspace.variables, mask_secrets);
+
+    for e in &layers.environments {
+        for f in layers.env_files.get(&e.id).into_iter().flatten() {
+            variables = add_variable_to_map(variables, &f.variables, mask_secrets);
         }
-        new_map.insert(k, rendered);
+        variables = add_variable_to_map(variables, &e.variables, mask_secrets);
     }
 
-    if did_render && render_count <= 3 {
-        new_map = Box::pin(recursively_render_variables(&new_map, render_count + 1, cb)).await;
+    for f in &layers.folders {
+        variables = add_variable_to_map(variables, &f.variables, mask_secrets);
     }
+    add_variable_to_map(variables, &layers.request_variables, mask_secrets)
+}
 
-    new_map
+/// The allowed process environment variables, as `env.NAME` variables. Their values are
+/// escaped, since they're used as they are instead of rendered as templates.
+fn process_env_variables(layers: &VariableLayers) -> HashMap<String, String> {
+    layers
+        .process_env
+        .iter()
+        .map(|(n, v)| (format!("{PROCESS_ENV_PREFIX}{n}"), escape_template(v)))
+        .collect()
 }
 
-pub async fn variables_from_environment<T: TemplateCallback>(
-    workspace: &Workspace,
-    environment: Option<&Environment>,
-    cb: &T,
+/// The variables, with typed ones formatted for inlining into JSON. Strings are escaped to
+/// go inside a JSON string, and other types become JSON literals. Values that don't match
+/// their type, like masked secrets, are left as-is.
+fn json_variables(
+    vars: &HashMap<String, String>,
+    layers: &VariableLayers,
 ) -> HashMap<String, String> {
-    let mut variables = HashMap::new();
-    variables = add_variable_to_map(variables, &workspace.variables);
+    // Follow the same precedence as variables_from_layers, so the type matches the value
+    let mut types = HashMap::new();
+    let mut add_types = |variables: &[EnvironmentVariable]| {
+        for v in variables
+            .iter()
+            .filter(|v| v.enabled && !v.value.is_empty())
+        {
+            types.insert(v.name.to_string(), v.value_type);
+        }
+    };
+    add_types(&layers.workspace.variables);
+    for e in &layers.environments {
+        for f in layers.env_files.get(&e.id).into_iter().flatten() {
+            add_types(&f.variables);
+        }
+        add_types(&e.variables);
+    }
+    for f in &layers.folders {
+        add_types(&f.variables);
+    }
+    add_types(&layers.request_variables);
 
-    if let Some(e) = environment {
-        variables = add_variable_to_map(variables, &e.variables);
+    let mut json_vars = vars.to_owned();
+    for (name, value) in json_vars.iter_mut() {
+        let value_type = match types.get(name.as_str()) {
+            Some(Some(t)) => t,
+            _ => continue,
+        };
+        if let Some(v) = format_json_variable(value, value_type) {
+            *value = v;
+        }
     }
 
-    recursively_render_variables(&variables, 0, cb).await
+    json_vars
+}
+
+fn format_json_variable(value: &str, value_type: &EnvironmentVariableType) -> Option<String> {
+    match value_type {
+        EnvironmentVariableType::String | EnvironmentVariableType::Multiline => {
+            let quoted = serde_json::to_string(value).ok()?;
+            Some(quoted[1..quoted.len() - 1].to_string())
+        }
+        EnvironmentVariableType::Number => serde_json::from_str::<serde_json::Number>(value.trim())
+            .ok()
+            .map(|n| n.to_string()),
+        EnvironmentVariableType::Boolean => {
+            value.trim().parse::<bool>().ok().map(|b| b.to_string())
+        }
+        EnvironmentVariableType::Json => serde_json::from_str::<Value>(value)
+            .ok()
+            .map(|_| value.to_string()),
+    }
 }
 
 pub async fn render<T: TemplateCallback>(
     template: &str,
     vars: &HashMap<String, String>,
     cb: &T,
-) -> String {
-    parse_and_render(template, vars, cb).await
+    opt: &RenderOptions,
+) -> Result<String> {
+    parse_and_render(template, vars, cb, opt).await
 }
 
 fn add_variable_to_map(
     m: HashMap<String, String>,
     variables: &Vec<EnvironmentVariable>,
+    mask_secrets: bool,
 ) -> HashMap<String, String> {
     let mut map = m.clone();
     for variable in variables {
@@ -154,8 +716,21 @@ fn add_variable_to_map(
             continue;
         }
         let name = variable.name.as_str();
-        let value = variable.value.as_str();
-        map.insert(name.into(), value.into());
+        // Masking before variables are resolved also masks anything derived from secrets
+        let value = match (variable.secret, mask_secrets) {
+            (true, true) => SECRET_MASK.to_string(),
+            (true, false) => match decrypt_secret(variable.value.as_str()) {
+                Some(value) => value,
+                None => {
+                    // Leave it undefined rather than sending the encrypted value
+                    warn!("Failed to decrypt secret variable {name}");
+                    map.remove(name);
+                    continue;
+                }
+            },
+            (false, _) => variable.value.to_string(),
+        };
+        map.insert(name.into(), value);
     }
 
     map
@@ -165,34 +740,43 @@ pub async fn render_json_value<T: TemplateCallback>(
     v: Value,
     vars: &HashMap<String, String>,
     cb: &T,
-) -> Value {
-    match v {
-        Value::String(s) => json!(render(s.as_str(), vars, cb).await),
+    opt: &RenderOptions,
+) -> Result<Value> {
+    let v = match v {
+        Value::String(s) => json!(render(s.as_str(), vars, cb, opt).await?),
         Value::Array(a) => {
             let mut new_a = Vec::new();
             for v in a {
-                new_a.push(Box::pin(render_json_value(v, vars, cb)).await)
+                new_a.push(Box::pin(render_json_value(v, vars, cb, opt)).await?)
             }
             json!(new_a)
         }
         Value::Object(o) => {
             let mut new_o = Map::new();
             for (k, v) in o {
-                let key = Box::pin(render(k.as_str(), vars, cb)).await;
-                let value = Box::pin(render_json_value(v, vars, cb)).await;
+                let key = Box::pin(render(k.as_str(), vars, cb, opt)).await?;
+                let value = Box::pin(render_json_value(v, vars, cb, opt)).await?;
                 new_o.insert(key, value);
             }
             json!(new_o)
         }
         v => v,
-    }
+    };
+
+    Ok(v)
 }
 
 #[cfg(test)]
 mod tests {
-    use serde_json::json;
+    use crate::render::VariableLayers;
+    use serde_json::{json, Value};
     use std::collections::HashMap;
-    use yaak_templates::TemplateCallback;
+    use yaak_models::models::{
+        Environment, EnvironmentVariable, EnvironmentVariableType, GrpcMetadataEntry, GrpcRequest,
+        HttpRequest, Workspace,
+    };
+    use yaak_models::secrets::SECRET_MASK;
+    use yaak_templates::{RenderOptions, TemplateCallback};
 
     struct EmptyCB {}
 
@@ -200,7 +784,7 @@ mod tests {
         async fn run(
             &self,
             _fn_name: &str,
-            _args: HashMap<String, String>,
+            _args: HashMap<String, Value>,
         ) -> Result<String, String> {
             todo!()
         }
@@ -212,8 +796,9 @@ mod tests {
         let mut vars = HashMap::new();
         vars.insert("a".to_string(), "aaa".to_string());
 
-        let result = super::render_json_value(v, &vars, &EmptyCB {}).await;
-        assert_eq!(result, json!("aaa"))
+        let result =
+            super::render_json_value(v, &vars, &EmptyCB {}, &RenderOptions::default()).await;
+        assert_eq!(result, Ok(json!("aaa")))
     }
 
     #[tokio::test]
@@ -222,8 +807,9 @@ mod tests {
         let mut vars = HashMap::new();
         vars.insert("a".to_string(), "aaa".to_string());
 
-        let result = super::render_json_value(v, &vars, &EmptyCB {}).await;
-        assert_eq!(result, json!(["aaa", "aaa"]))
+        let result =
+            super::render_json_value(v, &vars, &EmptyCB {}, &RenderOptions::default()).await;
+        assert_eq!(result, Ok(json!(["aaa", "aaa"])))
     }
 
     #[tokio::test]
@@ -232,8 +818,9 @@ mod tests {
         let mut vars = HashMap::new();
         vars.insert("a".to_string(), "aaa".to_string());
 
-        let result = super::render_json_value(v, &vars, &EmptyCB {}).await;
-        assert_eq!(result, json!({"aaa": "aaa"}))
+        let result =
+            super::render_json_value(v, &vars, &EmptyCB {}, &RenderOptions::default()).await;
+        assert_eq!(result, Ok(json!({"aaa": "aaa"})))
     }
 
     #[tokio::test]
@@ -249,14 +836,225 @@ mod tests {
         let mut vars = HashMap::new();
         vars.insert("a".to_string(), "aaa".to_string());
 
-        let result = super::render_json_value(v, &vars, &EmptyCB {}).await;
-        assert_eq!(result, json!([
+        let result =
+            super::render_json_value(v, &vars, &EmptyCB {}, &RenderOptions::default()).await;
+        assert_eq!(result, Ok(json!([
             123,
             {"aaa": "aaa"},
             null,
             "aaa",
             false,
             {"x": ["aaa"]}
-        ]))
+        ])))
+    }
+
+    #[tokio::test]
+    async fn json_variables() {
+        let variable = |name: &str, value: &str, value_type| EnvironmentVariable {
+            enabled: true,
+            name: name.to_string(),
+            value: value.to_string(),
+            value_type,
+            ..Default::default()
+        };
+        let layers = VariableLayers {
+            workspace: Default::default(),
+            environments: vec![Environment {
+                variables: vec![
+                    variable("name", "say \"hi\"", Some(EnvironmentVariableType::String)),
+                    variable("notes", "a\nb", Some(EnvironmentVariableType::Multiline)),
+                    variable("count", " 42 ", Some(EnvironmentVariableType::Number)),
+                    variable("enabled", "true", Some(EnvironmentVariableType::Boolean)),
+                    variable("ids", "[1, 2]", Some(EnvironmentVariableType::Json)),
+                    variable("masked", "••••", Some(EnvironmentVariableType::Number)),
+                    variable("raw", "\"as-is\"", None),
+                ],
+                ..Default::default()
+            }],
+            env_files: HashMap::new(),
+            folders: Vec::new(),
+            request_variables: Vec::new(),
+            process_env: HashMap::new(),
+        };
+        let vars = layers.environments[0]
+            .variables
+            .iter()
+            .map(|v| (v.name.to_string(), v.value.to_string()))
+            .collect();
+
+        let template = r#"{"name": "${[ name ]}", "notes": "${[ notes ]}", "count": ${[ count ]}, "enabled": ${[ enabled ]}, "ids": ${[ ids ]}, "raw": ${[ raw ]}}"#;
+        let json_vars = super::json_variables(&vars, &layers);
+        assert_eq!(json_vars["masked"], "••••");
+
+        let rendered = super::render(template, &json_vars, &EmptyCB {}, &RenderOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            serde_json::from_str::<Value>(&rendered).unwrap(),
+            json!({
+                "name": "say \"hi\"",
+                "notes": "a\nb",
+                "count": 42,
+                "enabled": true,
+                "ids": [1, 2],
+                "raw": "as-is"
+            })
+        );
+    }
+
+    #[test]
+    fn analyze_grpc_requests() {
+        let variable = |name: &str| EnvironmentVariable {
+            enabled: true,
+            name: name.to_string(),
+            value: "v".to_string(),
+            ..Default::default()
+        };
+        let layers = VariableLayers {
+            workspace: Default::default(),
+            environments: vec![Environment {
+                variables: vec![variable("host"), variable("token"), variable("unused")],
+                ..Default::default()
+            }],
+            env_files: HashMap::new(),
+            folders: Vec::new(),
+            request_variables: Vec::new(),
+            process_env: HashMap::new(),
+        };
+        let http_request = HttpRequest {
+            name: "http".to_string(),
+            url: "${[ host ]}/users".to_string(),
+            ..Default::default()
+        };
+        let grpc_request = GrpcRequest {
+            name: "grpc".to_string(),
+            url: "${[ host ]}".to_string(),
+            metadata: vec![GrpcMetadataEntry {
+                enabled: true,
+                name: "authorization".to_string(),
+                value: "${[ token ]}".to_string(),
+            }],
+            message: r#"{"id": "${[ id ]}"}"#.to_string(),
+            ..Default::default()
+        };
+
+        let references =
+            super::analyze_requests(&[http_request], &[grpc_request], &layers, &[], &[]);
+        let reference = |name: &str| references.variables.iter().find(|v| v.name == name);
+        assert_eq!(
+            reference("host").unwrap().locations,
+            vec!["http / url", "grpc / url"]
+        );
+        assert_eq!(
+            reference("token").unwrap().locations,
+            vec!["grpc / metadata.authorization"]
+        );
+        assert_eq!(reference("id").unwrap().locations, vec!["grpc / message"]);
+        assert!(!reference("id").unwrap().defined);
+        assert_eq!(references.unused_variables, vec!["unused".to_string()]);
+    }
+
+    #[test]
+    fn variable_paths() {
+        let vars = HashMap::from([
+            ("a".to_string(), "${[ b ]}-${[ c ]}".to_string()),
+            ("b".to_string(), "${[ env.HOME ]}".to_string()),
+            ("c".to_string(), "${[ a ]}".to_string()),
+        ]);
+        let mut paths = Vec::new();
+        super::variable_paths("a", &vars, &mut Vec::new(), &mut paths);
+        assert_eq!(paths, vec![vec!["a", "b", "env.HOME"], vec!["a", "c", "a"]]);
+    }
+
+    #[test]
+    fn secret_variables() {
+        let secret = |name: &str, value: &str| EnvironmentVariable {
+            enabled: true,
+            secret: true,
+            name: name.to_string(),
+            value: value.to_string(),
+            ..Default::default()
+        };
+        let variables = vec![secret("plain", "hunter2"), secret("broken", "yenc_v1:AAAA")];
+        let lower = HashMap::from([("broken".to_string(), "lower".to_string())]);
+
+        let masked = super::add_variable_to_map(lower.clone(), &variables, true);
+        assert_eq!(masked["plain"], SECRET_MASK);
+        assert_eq!(masked["broken"], SECRET_MASK);
+
+        // Secrets that can't be decrypted are undefined instead of sent encrypted
+        let sent = super::add_variable_to_map(lower, &variables, false);
+        assert_eq!(sent["plain"], "hunter2");
+        assert!(!sent.contains_key("broken"));
+    }
+
+    #[tokio::test]
+    async fn env_file_values_not_rendered() {
+        let dir = std::env::temp_dir().join(format!(
+            "yaak_env_file_test_{}_{}",
+            std::process::id(),
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(".env");
+        std::fs::write(&path, "TOKEN='${[ uuid_v4() ]}'\n").unwrap();
+        let file = crate::env_files::load_env_file(&path.to_string_lossy(), &path).await;
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let layers = VariableLayers {
+            workspace: Workspace::default(),
+            environments: vec![Environment {
+                id: "env".to_string(),
+                ..Default::default()
+            }],
+            env_files: HashMap::from([("env".to_string(), vec![file])]),
+            folders: Vec::new(),
+            request_variables: Vec::new(),
+            process_env: HashMap::new(),
+        };
+
+        // The callback would panic if the function were called
+        let template = "${[ TOKEN ]}";
+        let opt = &RenderOptions::strict();
+        let vars = super::variables_from_layers(&layers, &[template], &EmptyCB {}, opt, false)
+            .await
+            .unwrap();
+        assert_eq!(
+            super::render(template, &vars, &EmptyCB {}, opt).await,
+            Ok("${[ uuid_v4() ]}".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn process_env_variables() {
+        let layers = VariableLayers {
+            workspace: Workspace {
+                variables: vec![EnvironmentVariable {
+                    enabled: true,
+                    name: "auth".to_string(),
+                    value: "Bearer ${[ env.TOKEN ]}".to_string(),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            environments: Vec::new(),
+            env_files: HashMap::new(),
+            folders: Vec::new(),
+            request_variables: Vec::new(),
+            process_env: HashMap::from([("TOKEN".to_string(), "${[ not_a_tag ]}".to_string())]),
+        };
+
+        let template = "${[ auth ]} ${[ env.TOKEN ]} ${[ env.OTHER ?? 'unset' ]}";
+        let opt = &RenderOptions::strict();
+        let vars = super::variables_from_layers(&layers, &[template], &EmptyCB {}, opt, false)
+            .await
+            .unwrap();
+        assert_eq!(
+            super::render(template, &vars, &EmptyCB {}, opt).await,
+            Ok("Bearer ${[ not_a_tag ]} ${[ not_a_tag ]} unset".to_string())
+        );
     }
 }
@@ -1,13 +1,28 @@
+use crate::local_access::{
+    call_local_function, get_local_access, is_local_function, LOCAL_FUNCTIONS,
+};
+use serde_json::Value;
 use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
 use tauri::{AppHandle, Manager};
-use yaak_plugin_runtime::events::{RenderPurpose, TemplateFunctionArg};
+use tokio::sync::Mutex;
+use yaak_plugin_runtime::events::{
+    RenderPurpose, TemplateFunction, TemplateFunctionArg, TemplateFunctionBaseArg,
+    TemplateFunctionTextArg,
+};
 use yaak_plugin_runtime::manager::PluginManager;
+use yaak_templates::native::{find_native_function, NATIVE_FUNCTIONS};
 use yaak_templates::TemplateCallback;
 
 #[derive(Clone)]
 pub struct PluginTemplateCallback {
     app_handle: AppHandle,
     purpose: RenderPurpose,
+    // Needed to look up the workspace's allowlist for local files and commands
+    workspace_id: Option<String>,
+    // Results of function calls made while sending, so identical calls only run once
+    memo: Option<CallMemo>,
 }
 
 impl PluginTemplateCallback {
@@ -15,48 +30,85 @@ impl PluginTemplateCallback {
         PluginTemplateCallback {
             app_handle,
             purpose: RenderPurpose::Preview,
+            workspace_id: None,
+            memo: None,
         }
     }
 
+    pub fn for_workspace(&self, workspace_id: &str) -> PluginTemplateCallback {
+        let mut v = self.clone();
+        v.workspace_id = Some(workspace_id.to_string());
+        v
+    }
+
     pub fn for_send(&self) -> PluginTemplateCallback {
         let mut v = self.clone();
         v.purpose = RenderPurpose::Send;
+        v.memo = Some(CallMemo::default());
         v
     }
-}
 
-impl TemplateCallback for PluginTemplateCallback {
-    async fn run(&self, fn_name: &str, args: HashMap<String, String>) -> Result<String, String> {
-        // The beta named the function `Response` but was changed in stable.
-        // Keep this here for a while because there's no easy way to migrate
-        let fn_name = if fn_name == "Response" {
-            "response"
-        } else {
-            fn_name
-        };
-        
+    pub fn app_handle(&self) -> &AppHandle {
+        &self.app_handle
+    }
+
+    /// Secrets are only revealed when rendering to send
+    pub fn masks_secrets(&self) -> bool {
+        matches!(self.purpose, RenderPurpose::Preview)
+    }
+
+    async fn find_function(&self, fn_name: &str) -> Result<TemplateFunction, String> {
         let plugin_manager = self.app_handle.state::<PluginManager>();
-        let function = plugin_manager
+        plugin_manager
             .get_template_functions()
             .await
             .map_err(|e| e.to_string())?
             .iter()
             .flat_map(|f| f.functions.clone())
             .find(|f| f.name == fn_name)
-            .ok_or("")?;
+            .ok_or(format!("Template function {fn_name} not found"))
+    }
+
+    /// Only calls that always return the same value can be shared, so things like
+    /// `uuid_v4()` still produce a new value each time they appear
+    async fn is_deterministic(&self, fn_name: &str) -> bool {
+        let fn_name = migrate_fn_name(fn_name);
+        match self.find_function(fn_name).await {
+            Ok(f) => is_deterministic(&f),
+            Err(_) => native_template_functions()
+                .iter()
+                .find(|f| f.name == fn_name)
+                .is_some_and(is_deterministic),
+        }
+    }
+
+    async fn call(&self, fn_name: &str, args: HashMap<String, Value>) -> Result<String, String> {
+        let fn_name = migrate_fn_name(fn_name);
+        let plugin_manager = self.app_handle.state::<PluginManager>();
+
+        // Plugin functions take precedence, and native ones are used when no plugin
+        // defines the function or the plugin runtime isn't available
+        let function = match self.find_function(fn_name).await {
+            Ok(f) => f,
+            Err(e) => {
+                if is_local_function(fn_name) {
+                    return self.call_local(fn_name, args).await;
+                }
+                return match find_native_function(fn_name) {
+                    Some(f) => f.call(&args),
+                    None => Err(e),
+                };
+            }
+        };
 
         let mut args_with_defaults = args.clone();
-        
+
         // Fill in default values for all args
         for a_def in function.args {
-            let base = match a_def {
-                TemplateFunctionArg::Text(a) => a.base,
-                TemplateFunctionArg::Select(a) => a.base,
-                TemplateFunctionArg::Checkbox(a) => a.base,
-                TemplateFunctionArg::HttpRequest(a) => a.base,
-            };
-            if let None = args_with_defaults.get(base.name.as_str()) {
-                args_with_defaults.insert(base.name, base.default_value.unwrap_or_default());
+            let base = arg_base(a_def);
+            if let None | Some(Value::Null) = args_with_defaults.get(base.name.as_str()) {
+                let default_value = base.default_value.unwrap_or_default();
+                args_with_defaults.insert(base.name, Value::String(default_value));
             }
         }
 
@@ -66,4 +118,197 @@ impl TemplateCallback for PluginTemplateCallback {
             .map_err(|e| e.to_string())?;
         Ok(resp.unwrap_or_default())
     }
+
+    /// Local files and commands are only used when the workspace allows them, and only
+    /// while sending unless it also allows them in previews
+    async fn call_local(
+        &self,
+        fn_name: &str,
+        args: HashMap<String, Value>,
+    ) -> Result<String, String> {
+        let workspace_id = self
+            .workspace_id
+            .as_deref()
+            .ok_or(format!("{fn_name}() can only be used within a workspace"))?;
+        let settings = get_local_access(&self.app_handle, workspace_id).await;
+        if matches!(self.purpose, RenderPurpose::Preview) && !settings.allow_in_preview {
+            return Err(format!("{fn_name}() only runs when sending"));
+        }
+        call_local_function(fn_name, &args, &settings).await
+    }
+}
+
+/// Definitions for the built-in functions, so they show up alongside plugin functions
+pub fn native_template_functions() -> Vec<TemplateFunction> {
+    let native = NATIVE_FUNCTIONS
+        .iter()
+        .map(|f| (f.name, f.args, f.deterministic));
+    // Local functions keep their own cache of outputs, so repeating them is consistent too
+    let local = LOCAL_FUNCTIONS
+        .iter()
+        .map(|(name, args)| (*name, *args, true));
+    native
+        .chain(local)
+        .map(|(name, args, deterministic)| TemplateFunction {
+            name: name.to_string(),
+            deterministic: Some(deterministic),
+            args: args
+                .iter()
+                .map(|a| {
+                    TemplateFunctionArg::Text(TemplateFunctionTextArg {
+                        base: TemplateFunctionBaseArg {
+                            name: a.to_string(),
+                            optional: Some(true),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    })
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// Plugin functions that don't say whether they're deterministic are assumed to be, since
+/// most of them read values that don't change during a send
+fn is_deterministic(f: &TemplateFunction) -> bool {
+    f.deterministic.unwrap_or(true)
+}
+
+/// Results of function calls, keyed by function and args
+#[derive(Clone, Default)]
+pub struct CallMemo(Arc<Mutex<HashMap<String, String>>>);
+
+impl CallMemo {
+    /// The result of an identical earlier call, or of making the call now
+    pub async fn get_or_call(
+        &self,
+        fn_name: &str,
+        args: &HashMap<String, Value>,
+        call: impl Future<Output = Result<String, String>>,
+    ) -> Result<String, String> {
+        // serde_json sorts object keys, so identical args always produce the same key
+        let key = format!(
+            "{fn_name}({})",
+            Value::Object(args.clone().into_iter().collect())
+        );
+        if let Some(v) = self.0.lock().await.get(&key) {
+            return Ok(v.to_owned());
+        }
+
+        let v = call.await?;
+        self.0.lock().await.insert(key, v.clone());
+        Ok(v)
+    }
+}
+
+fn arg_base(a_def: TemplateFunctionArg) -> TemplateFunctionBaseArg {
+    match a_def {
+        TemplateFunctionArg::Text(a) => a.base,
+        TemplateFunctionArg::Select(a) => a.base,
+        TemplateFunctionArg::Checkbox(a) => a.base,
+        TemplateFunctionArg::HttpRequest(a) => a.base,
+    }
+}
+
+// The beta named the function `Response` but was changed in stable.
+// Keep this here for a while because there's no easy way to migrate
+fn migrate_fn_name(fn_name: &str) -> &str {
+    if fn_name == "Response" {
+        "response"
+    } else {
+        fn_name
+    }
+}
+
+impl TemplateCallback for PluginTemplateCallback {
+    async fn run(&self, fn_name: &str, args: HashMap<String, Value>) -> Result<String, String> {
+        match &self.memo {
+            Some(memo) if self.is_deterministic(fn_name).await => {
+                memo.get_or_call(fn_name, &args.clone(), self.call(fn_name, args))
+                    .await
+            }
+            _ => self.call(fn_name, args).await,
+        }
+    }
+
+    async fn arg_names(&self, fn_name: &str) -> Vec<String> {
+        match self.find_function(migrate_fn_name(fn_name)).await {
+            Ok(f) => f.args.into_iter().map(|a| arg_base(a).name).collect(),
+            Err(_) => native_template_functions()
+                .into_iter()
+                .find(|f| f.name == fn_name)
+                .map(|f| f.args.into_iter().map(|a| arg_base(a).name).collect())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::template_callback::{is_deterministic, CallMemo};
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use yaak_plugin_runtime::events::TemplateFunction;
+    use yaak_templates::{parse_and_render, RenderOptions, TemplateCallback};
+
+    /// Calls functions the way a plugin would, sharing results while sending
+    struct PluginCB {
+        functions: Vec<TemplateFunction>,
+        memo: CallMemo,
+        calls: AtomicUsize,
+    }
+
+    impl PluginCB {
+        async fn call(
+            &self,
+            fn_name: &str,
+            args: &HashMap<String, Value>,
+        ) -> Result<String, String> {
+            let n = self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(format!("{fn_name}:{}:{n}", args["value"]))
+        }
+    }
+
+    impl TemplateCallback for PluginCB {
+        async fn run(&self, fn_name: &str, args: HashMap<String, Value>) -> Result<String, String> {
+            let f = self.functions.iter().find(|f| f.name == fn_name).unwrap();
+            match is_deterministic(f) {
+                true => {
+                    self.memo
+                        .get_or_call(fn_name, &args, self.call(fn_name, &args))
+                        .await
+                }
+                false => self.call(fn_name, &args).await,
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn memoized_plugin_calls() {
+        // Existing plugins don't declare whether their functions are deterministic
+        let functions = vec![
+            serde_json::from_value(json!({"name": "hash", "args": []})).unwrap(),
+            serde_json::from_value(json!({"name": "random", "args": [], "deterministic": false}))
+                .unwrap(),
+        ];
+        let cb = PluginCB {
+            functions,
+            memo: CallMemo::default(),
+            calls: AtomicUsize::new(0),
+        };
+
+        let template = "${[ hash(value='a') ]} ${[ hash(value='a') ]} ${[ hash(value='b') ]}";
+        let result = parse_and_render(template, &HashMap::new(), &cb, &RenderOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(result, "hash:\"a\":0 hash:\"a\":0 hash:\"b\":1");
+
+        let template = "${[ random(value='a') ]} ${[ random(value='a') ]}";
+        let result = parse_and_render(template, &HashMap::new(), &cb, &RenderOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(result, "random:\"a\":2 random:\"a\":3");
+    }
 }
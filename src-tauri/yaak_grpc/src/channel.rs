@@ -0,0 +1,111 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use hyper::service::Service;
+use hyper::{Body, Client, Request, Response};
+use tonic::body::BoxBody;
+use tonic::transport::Uri;
+
+use crate::proto::{get_transport, https_connector, GrpcTransport};
+use crate::web::{BoxError, GrpcWebClient};
+use crate::{ChannelConfig, ChannelState, GrpcProtocol};
+
+/// Calls the server over native gRPC or gRPC-Web, depending on the request's protocol. Clones
+/// share the underlying connections, along with their state.
+#[derive(Clone)]
+pub struct GrpcChannel {
+    client: ChannelClient,
+    /// State of the last call, used while no connection is open
+    status: Arc<Mutex<ChannelState>>,
+    open_connections: Arc<AtomicUsize>,
+}
+
+#[derive(Clone)]
+enum ChannelClient {
+    Native(GrpcTransport),
+    Web(GrpcWebClient),
+}
+
+impl GrpcChannel {
+    pub fn new(uri: &Uri, config: &ChannelConfig) -> Result<Self, String> {
+        let open_connections = Arc::new(AtomicUsize::new(0));
+        let client = match config.protocol {
+            GrpcProtocol::Grpc => {
+                ChannelClient::Native(get_transport(uri, config, open_connections.clone())?)
+            }
+            protocol => {
+                let mut builder = Client::builder();
+                if let Some(timeout) = config.idle_timeout {
+                    builder.pool_idle_timeout(timeout);
+                }
+                let connector = https_connector(uri, &config.tls, false, open_connections.clone())?;
+                let client = builder.build(connector);
+                let text = protocol == GrpcProtocol::GrpcWebText;
+                ChannelClient::Web(GrpcWebClient::new(client, text))
+            }
+        };
+
+        Ok(GrpcChannel {
+            client,
+            status: Arc::new(Mutex::new(ChannelState::Idle)),
+            open_connections,
+        })
+    }
+
+    pub fn is_web(&self) -> bool {
+        matches!(self.client, ChannelClient::Web(_))
+    }
+
+    pub fn state(&self) -> ChannelState {
+        if self.open_connections.load(Ordering::SeqCst) > 0 {
+            return ChannelState::Ready;
+        }
+        match *self.status.lock().unwrap() {
+            // The connection the last call used has since been closed
+            ChannelState::Ready => ChannelState::Idle,
+            state => state,
+        }
+    }
+
+    fn set_state(status: &Mutex<ChannelState>, state: ChannelState) {
+        *status.lock().unwrap() = state;
+    }
+}
+
+impl Service<Request<BoxBody>> for GrpcChannel {
+    type Response = Response<Body>;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<BoxBody>) -> Self::Future {
+        if self.state() != ChannelState::Ready {
+            GrpcChannel::set_state(&self.status, ChannelState::Connecting);
+        }
+
+        let response: Self::Future = match &mut self.client {
+            ChannelClient::Native(client) => {
+                let response = client.request(req);
+                Box::pin(async move { Ok(response.await?) })
+            }
+            ChannelClient::Web(client) => client.call(req),
+        };
+
+        let status = self.status.clone();
+        Box::pin(async move {
+            let result = response.await;
+            let state = match &result {
+                Ok(_) => ChannelState::Ready,
+                Err(_) => ChannelState::TransientFailure,
+            };
+            GrpcChannel::set_state(&status, state);
+            result
+        })
+    }
+}
@@ -1,11 +1,17 @@
+use std::time::Duration;
+
 use prost_reflect::{DynamicMessage, MethodDescriptor, SerializeOptions};
 use serde::{Deserialize, Serialize};
 use serde_json::Deserializer;
+use yaak_models::models::GrpcTlsConfig;
 
+mod channel;
 mod codec;
 mod json_schema;
 pub mod manager;
 mod proto;
+mod status;
+mod web;
 
 pub use tonic::metadata::*;
 pub use tonic::Code;
@@ -30,6 +36,116 @@ pub struct MethodDefinition {
     pub server_streaming: bool,
 }
 
+/// Which version of the server reflection service was used to list services
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReflectionVersion {
+    V1,
+    V1alpha,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ReflectResponse {
+    pub services: Vec<ServiceDefinition>,
+    /// Empty when the services came from proto files instead of reflection
+    pub reflection_version: Option<ReflectionVersion>,
+}
+
+/// The wire protocol to call the server with
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum GrpcProtocol {
+    #[default]
+    Grpc,
+    /// gRPC-Web with binary bodies, over HTTP/1.1
+    GrpcWeb,
+    /// gRPC-Web with base64 encoded bodies, over HTTP/1.1
+    GrpcWebText,
+}
+
+/// Everything needed to open a channel to a server. Channels are reused by calls with the
+/// same URL and config.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq, Hash)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ChannelConfig {
+    pub protocol: GrpcProtocol,
+    pub tls: TlsConfig,
+    /// How often to ping the server over HTTP/2 to keep the connection alive
+    pub keepalive_interval: Option<Duration>,
+    /// How long to wait for a ping to be acknowledged before closing the connection
+    pub keepalive_timeout: Option<Duration>,
+    /// How long an unused connection stays open
+    pub idle_timeout: Option<Duration>,
+}
+
+/// Connectivity of a channel, like gRPC's channel states
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelState {
+    /// No connection is open
+    Idle,
+    Connecting,
+    Ready,
+    /// The last attempt to connect or call failed
+    TransientFailure,
+}
+
+impl ChannelState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChannelState::Idle => "idle",
+            ChannelState::Connecting => "connecting",
+            ChannelState::Ready => "ready",
+            ChannelState::TransientFailure => "transient_failure",
+        }
+    }
+}
+
+/// Options applied to each call
+#[derive(Debug, Default, Clone)]
+pub struct CallOptions {
+    /// How long the call may take, which is also sent to the server as `grpc-timeout`
+    pub deadline: Option<Duration>,
+    pub send_gzip: bool,
+    pub accept_gzip: bool,
+    pub max_send_size: Option<usize>,
+    pub max_receive_size: Option<usize>,
+    /// Wait for the server to accept connections instead of failing right away
+    pub wait_for_ready: bool,
+}
+
+/// How to secure connections to the server, for `https` URLs
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq, Hash)]
+#[serde(default, rename_all = "camelCase")]
+pub struct TlsConfig {
+    /// Accept any server certificate
+    pub skip_verify: bool,
+    /// PEM files of CA certificates to trust, in addition to the system's
+    pub ca_cert_paths: Vec<String>,
+    /// PEM files of the client certificate chain and its private key, for mutual TLS
+    pub client_cert_path: Option<String>,
+    pub client_key_path: Option<String>,
+    /// Name to send for SNI and verify the certificate against, instead of the URL's host
+    pub server_name: Option<String>,
+    /// Authority to send with requests, instead of the URL's host and port. The connection
+    /// is still made to the URL.
+    pub authority: Option<String>,
+}
+
+impl From<&GrpcTlsConfig> for TlsConfig {
+    fn from(tls: &GrpcTlsConfig) -> Self {
+        TlsConfig {
+            skip_verify: tls.skip_verify,
+            ca_cert_paths: tls.ca_cert_paths.clone(),
+            client_cert_path: tls.client_cert_path.clone(),
+            client_key_path: tls.client_key_path.clone(),
+            server_name: tls.server_name.clone(),
+            authority: tls.authority.clone(),
+        }
+    }
+}
+
 static SERIALIZE_OPTIONS: &'static SerializeOptions = &SerializeOptions::new()
     .skip_default_fields(false)
     .stringify_64_bit_integers(false);
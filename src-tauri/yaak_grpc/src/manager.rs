@@ -1,33 +1,50 @@
 use std::collections::HashMap;
+use std::future::Future;
 use std::path::PathBuf;
+use std::pin::Pin;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
 
-use hyper::client::HttpConnector;
-use hyper::Client;
-use hyper_rustls::HttpsConnector;
+use log::debug;
 pub use prost_reflect::DynamicMessage;
 use prost_reflect::{DescriptorPool, MethodDescriptor, ServiceDescriptor};
-use serde_json::Deserializer;
-use tauri::AppHandle;
+use serde_json::{Deserializer, Value};
+use tokio::sync::mpsc::Receiver;
 use tokio_stream::wrappers::ReceiverStream;
-use tonic::body::BoxBody;
-use tonic::metadata::{MetadataKey, MetadataValue};
+use tokio_stream::Stream;
+use tonic::client::Grpc;
+use tonic::codec::CompressionEncoding;
+use tonic::metadata::{MetadataKey, MetadataMap, MetadataValue};
 use tonic::transport::Uri;
-use tonic::{IntoRequest, IntoStreamingRequest, Request, Response, Status, Streaming};
+use tonic::{Code, Request, Response, Status, Streaming};
 
+use crate::channel::GrpcChannel;
 use crate::codec::DynamicCodec;
 use crate::proto::{
-    fill_pool_from_files, fill_pool_from_reflection, get_transport, method_desc_to_path,
+    fill_pool_from_files, fill_pool_from_reflection, method_desc_to_path, request_origin,
+};
+use crate::status::decode_status_details;
+use crate::{
+    json_schema, CallOptions, ChannelConfig, ChannelState, MethodDefinition, ReflectResponse,
+    ReflectionVersion, ServiceDefinition,
 };
-use crate::{json_schema, MethodDefinition, ServiceDefinition};
 
 #[derive(Clone)]
 pub struct GrpcConnection {
     pool: DescriptorPool,
-    conn: Client<HttpsConnector<HttpConnector>, BoxBody>,
+    conn: GrpcChannel,
+    /// Origin requests are sent to, which differs from the URL if the authority is overridden
     pub uri: Uri,
 }
 
+const INITIAL_READY_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_READY_BACKOFF: Duration = Duration::from_secs(5);
+/// How long calls without a deadline wait for the server to be ready
+const MAX_READY_WAIT: Duration = Duration::from_secs(60);
+
 #[derive(Default)]
 pub struct StreamError {
     pub message: String,
@@ -70,12 +87,48 @@ impl GrpcConnection {
         Ok(method)
     }
 
+    fn client(&self, options: &CallOptions) -> Grpc<GrpcChannel> {
+        let mut client = Grpc::with_origin(self.conn.clone(), self.uri.clone());
+        if options.send_gzip {
+            client = client.send_compressed(CompressionEncoding::Gzip);
+        }
+        if options.accept_gzip {
+            client = client.accept_compressed(CompressionEncoding::Gzip);
+        }
+        if let Some(limit) = options.max_send_size {
+            client = client.max_encoding_message_size(limit);
+        }
+        if let Some(limit) = options.max_receive_size {
+            client = client.max_decoding_message_size(limit);
+        }
+        client
+    }
+
+    /// Details of a failed call's status, like `BadRequest`, as JSON
+    pub fn status_details(&self, status: &Status) -> Vec<Value> {
+        decode_status_details(status, &self.pool)
+    }
+
+    /// State of the channel the connection's calls are made on
+    pub fn state(&self) -> ChannelState {
+        self.conn.state()
+    }
+
+    /// gRPC-Web sends the whole request body at once, so clients can't stream messages
+    fn check_client_streaming(&self) -> Result<(), String> {
+        match self.conn.is_web() {
+            true => Err("gRPC-Web doesn't support client streaming".to_string()),
+            false => Ok(()),
+        }
+    }
+
     pub async fn unary(
         &self,
         service: &str,
         method: &str,
         message: &str,
         metadata: HashMap<String, String>,
+        options: &CallOptions,
     ) -> Result<Response<DynamicMessage>, StreamError> {
         let method = &self.method(&service, &method)?;
         let input_message = method.input();
@@ -85,16 +138,27 @@ impl GrpcConnection {
             .map_err(|e| e.to_string())?;
         deserializer.end().unwrap();
 
-        let mut client = tonic::client::Grpc::with_origin(self.conn.clone(), self.uri.clone());
-
-        let mut req = req_message.into_request();
-        decorate_req(metadata, &mut req).map_err(|e| e.to_string())?;
-
+        let client = self.client(options);
+        let metadata = metadata_map(metadata)?;
         let path = method_desc_to_path(method);
         let codec = DynamicCodec::new(method.clone());
-        client.ready().await.unwrap();
-
-        Ok(client.unary(req, path, codec).await?)
+        let call = retry_unavailable(
+            options,
+            || true,
+            || {
+                let mut client = client.clone();
+                let req = new_request(req_message.clone(), &metadata, options);
+                let (path, codec) = (path.clone(), codec.clone());
+                async move {
+                    client
+                        .ready()
+                        .await
+                        .map_err(|e| Status::unavailable(e.to_string()))?;
+                    client.unary(req, path, codec).await
+                }
+            },
+        );
+        Ok(with_deadline(options, call).await?)
     }
 
     pub async fn streaming(
@@ -103,18 +167,32 @@ impl GrpcConnection {
         method: &str,
         stream: ReceiverStream<DynamicMessage>,
         metadata: HashMap<String, String>,
+        options: &CallOptions,
     ) -> Result<Response<Streaming<DynamicMessage>>, StreamError> {
+        self.check_client_streaming()?;
         let method = &self.method(&service, &method)?;
-        let mut client = tonic::client::Grpc::with_origin(self.conn.clone(), self.uri.clone());
-
-        let mut req = stream.into_streaming_request();
-
-        decorate_req(metadata, &mut req).map_err(|e| e.to_string())?;
-
+        let client = self.client(options);
+        let messages = SharedReceiver::new(stream);
+        let metadata = metadata_map(metadata)?;
         let path = method_desc_to_path(method);
         let codec = DynamicCodec::new(method.clone());
-        client.ready().await.map_err(|e| e.to_string())?;
-        Ok(client.streaming(req, path, codec).await?)
+        let call = retry_unavailable(
+            options,
+            || !messages.was_sent(),
+            || {
+                let mut client = client.clone();
+                let req = new_request(messages.clone(), &metadata, options);
+                let (path, codec) = (path.clone(), codec.clone());
+                async move {
+                    client
+                        .ready()
+                        .await
+                        .map_err(|e| Status::unavailable(e.to_string()))?;
+                    client.streaming(req, path, codec).await
+                }
+            },
+        );
+        Ok(with_deadline(options, call).await?)
     }
 
     pub async fn client_streaming(
@@ -123,22 +201,32 @@ impl GrpcConnection {
         method: &str,
         stream: ReceiverStream<DynamicMessage>,
         metadata: HashMap<String, String>,
+        options: &CallOptions,
     ) -> Result<Response<DynamicMessage>, StreamError> {
+        self.check_client_streaming()?;
         let method = &self.method(&service, &method)?;
-        let mut client = tonic::client::Grpc::with_origin(self.conn.clone(), self.uri.clone());
-        let mut req = stream.into_streaming_request();
-        decorate_req(metadata, &mut req).map_err(|e| e.to_string())?;
-
+        let client = self.client(options);
+        let messages = SharedReceiver::new(stream);
+        let metadata = metadata_map(metadata)?;
         let path = method_desc_to_path(method);
         let codec = DynamicCodec::new(method.clone());
-        client.ready().await.unwrap();
-        client
-            .client_streaming(req, path, codec)
-            .await
-            .map_err(|e| StreamError {
-                message: e.message().to_string(),
-                status: Some(e),
-            })
+        let call = retry_unavailable(
+            options,
+            || !messages.was_sent(),
+            || {
+                let mut client = client.clone();
+                let req = new_request(messages.clone(), &metadata, options);
+                let (path, codec) = (path.clone(), codec.clone());
+                async move {
+                    client
+                        .ready()
+                        .await
+                        .map_err(|e| Status::unavailable(e.to_string()))?;
+                    client.client_streaming(req, path, codec).await
+                }
+            },
+        );
+        Ok(with_deadline(options, call).await?)
     }
 
     pub async fn server_streaming(
@@ -147,6 +235,7 @@ impl GrpcConnection {
         method: &str,
         message: &str,
         metadata: HashMap<String, String>,
+        options: &CallOptions,
     ) -> Result<Response<Streaming<DynamicMessage>>, StreamError> {
         let method = &self.method(&service, &method)?;
         let input_message = method.input();
@@ -156,29 +245,81 @@ impl GrpcConnection {
             .map_err(|e| e.to_string())?;
         deserializer.end().unwrap();
 
-        let mut client = tonic::client::Grpc::with_origin(self.conn.clone(), self.uri.clone());
-
-        let mut req = req_message.into_request();
-        decorate_req(metadata, &mut req).map_err(|e| e.to_string())?;
-
+        let client = self.client(options);
+        let metadata = metadata_map(metadata)?;
         let path = method_desc_to_path(method);
         let codec = DynamicCodec::new(method.clone());
-        client.ready().await.map_err(|e| e.to_string())?;
-        Ok(client.server_streaming(req, path, codec).await?)
+        let call = retry_unavailable(
+            options,
+            || true,
+            || {
+                let mut client = client.clone();
+                let req = new_request(req_message.clone(), &metadata, options);
+                let (path, codec) = (path.clone(), codec.clone());
+                async move {
+                    client
+                        .ready()
+                        .await
+                        .map_err(|e| Status::unavailable(e.to_string()))?;
+                    client.server_streaming(req, path, codec).await
+                }
+            },
+        );
+        Ok(with_deadline(options, call).await?)
     }
 }
 
+/// Messages streamed from the client, shared by each attempt at the call. Calls are only
+/// retried while no message has been sent, so none are lost.
+#[derive(Clone)]
+struct SharedReceiver {
+    rx: Arc<Mutex<Receiver<DynamicMessage>>>,
+    sent: Arc<AtomicBool>,
+}
+
+impl SharedReceiver {
+    fn new(stream: ReceiverStream<DynamicMessage>) -> Self {
+        SharedReceiver {
+            rx: Arc::new(Mutex::new(stream.into_inner())),
+            sent: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    fn was_sent(&self) -> bool {
+        self.sent.load(Ordering::SeqCst)
+    }
+}
+
+impl Stream for SharedReceiver {
+    type Item = DynamicMessage;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let item = self.rx.lock().unwrap().poll_recv(cx);
+        if let Poll::Ready(Some(_)) = item {
+            self.sent.store(true, Ordering::SeqCst);
+        }
+        item
+    }
+}
+
+type ChannelKey = (String, ChannelConfig);
+
+#[derive(Default)]
 pub struct GrpcHandle {
-    app_handle: AppHandle,
     pools: HashMap<String, DescriptorPool>,
+    /// Open channels, reused by calls to the same URL with the same config
+    channels: HashMap<ChannelKey, GrpcChannel>,
+    /// The channel each request last used, so channels no request uses anymore are dropped
+    request_channels: HashMap<String, ChannelKey>,
 }
 
 impl GrpcHandle {
-    pub fn new(app_handle: &AppHandle) -> Self {
+    pub fn new() -> Self {
         let pools = HashMap::new();
         Self {
             pools,
-            app_handle: app_handle.clone(),
+            channels: HashMap::new(),
+            request_channels: HashMap::new(),
         }
     }
 }
@@ -189,17 +330,28 @@ impl GrpcHandle {
         id: &str,
         uri: &str,
         proto_files: &Vec<PathBuf>,
-    ) -> Result<(), String> {
-        let pool = if proto_files.is_empty() {
+        proto_includes: &Vec<PathBuf>,
+        channel: &ChannelConfig,
+        metadata: &HashMap<String, String>,
+    ) -> Result<Option<ReflectionVersion>, String> {
+        let (pool, version) = if proto_files.is_empty() {
             let full_uri = uri_from_str(uri)?;
-            fill_pool_from_reflection(&full_uri).await
+            let origin = request_origin(&full_uri, &channel.tls)?;
+            let channel = self.channel(id, &full_uri, channel)?;
+            let (pool, version) = fill_pool_from_reflection(channel, origin, metadata).await?;
+            (pool, Some(version))
         } else {
-            fill_pool_from_files(&self.app_handle, proto_files).await
-        }?;
+            (
+                fill_pool_from_files(proto_files, proto_includes).await?,
+                None,
+            )
+        };
 
-        self.pools
-            .insert(make_pool_key(id, uri, proto_files), pool.clone());
-        Ok(())
+        self.pools.insert(
+            make_pool_key(id, uri, proto_files, proto_includes),
+            pool.clone(),
+        );
+        Ok(version)
     }
 
     pub async fn services(
@@ -207,14 +359,22 @@ impl GrpcHandle {
         id: &str,
         uri: &str,
         proto_files: &Vec<PathBuf>,
-    ) -> Result<Vec<ServiceDefinition>, String> {
+        proto_includes: &Vec<PathBuf>,
+        channel: &ChannelConfig,
+        metadata: &HashMap<String, String>,
+    ) -> Result<ReflectResponse, String> {
         // Ensure reflection is up-to-date
-        self.reflect(id, uri, proto_files).await?;
+        let reflection_version = self
+            .reflect(id, uri, proto_files, proto_includes, channel, metadata)
+            .await?;
 
         let pool = self
-            .get_pool(id, uri, proto_files)
+            .get_pool(id, uri, proto_files, proto_includes)
             .ok_or("Failed to get pool".to_string())?;
-        Ok(self.services_from_pool(&pool))
+        Ok(ReflectResponse {
+            services: self.services_from_pool(&pool),
+            reflection_version,
+        })
     }
 
     fn services_from_pool(&self, pool: &DescriptorPool) -> Vec<ServiceDefinition> {
@@ -247,28 +407,80 @@ impl GrpcHandle {
         id: &str,
         uri: &str,
         proto_files: &Vec<PathBuf>,
+        proto_includes: &Vec<PathBuf>,
+        channel: &ChannelConfig,
+        metadata: &HashMap<String, String>,
     ) -> Result<GrpcConnection, String> {
-        self.reflect(id, uri, proto_files).await?;
+        // Descriptors are loaded once and reused, and `services` reloads them when asked to
+        if self
+            .get_pool(id, uri, proto_files, proto_includes)
+            .is_none()
+        {
+            self.reflect(id, uri, proto_files, proto_includes, channel, metadata)
+                .await?;
+        }
         let pool = self
-            .get_pool(id, uri, proto_files)
-            .ok_or("Failed to get pool")?;
+            .get_pool(id, uri, proto_files, proto_includes)
+            .ok_or("Failed to get pool")?
+            .clone();
 
         let uri = uri_from_str(uri)?;
-        let conn = get_transport();
+        let conn = self.channel(id, &uri, channel)?;
         let connection = GrpcConnection {
-            pool: pool.clone(),
+            pool,
             conn,
-            uri,
+            uri: request_origin(&uri, &channel.tls)?,
         };
         Ok(connection)
     }
 
-    fn get_pool(&self, id: &str, uri: &str, proto_files: &Vec<PathBuf>) -> Option<&DescriptorPool> {
-        self.pools.get(make_pool_key(id, uri, proto_files).as_str())
+    /// Get the open channel for the URL and config, or open a new one
+    fn channel(
+        &mut self,
+        id: &str,
+        uri: &Uri,
+        config: &ChannelConfig,
+    ) -> Result<GrpcChannel, String> {
+        let key = (uri.to_string(), config.clone());
+        self.request_channels.insert(id.to_string(), key.clone());
+
+        // Forget channels with no open connection, since they'd reconnect anyway, along with
+        // ones for URLs or configs no request uses anymore. Calls still in progress keep
+        // their own handle to the channel.
+        let in_use = self.request_channels.values().collect::<Vec<&ChannelKey>>();
+        self.channels.retain(|k, c| {
+            in_use.contains(&k)
+                && !matches!(
+                    c.state(),
+                    ChannelState::Idle | ChannelState::TransientFailure
+                )
+        });
+
+        if let Some(channel) = self.channels.get(&key) {
+            return Ok(channel.clone());
+        }
+
+        let channel = GrpcChannel::new(uri, config)?;
+        self.channels.insert(key, channel.clone());
+        Ok(channel)
+    }
+
+    fn get_pool(
+        &self,
+        id: &str,
+        uri: &str,
+        proto_files: &Vec<PathBuf>,
+        proto_includes: &Vec<PathBuf>,
+    ) -> Option<&DescriptorPool> {
+        self.pools
+            .get(make_pool_key(id, uri, proto_files, proto_includes).as_str())
     }
 }
 
-fn decorate_req<T>(metadata: HashMap<String, String>, req: &mut Request<T>) -> Result<(), String> {
+pub(crate) fn decorate_req<T>(
+    metadata: HashMap<String, String>,
+    req: &mut Request<T>,
+) -> Result<(), String> {
     for (k, v) in metadata {
         req.metadata_mut().insert(
             MetadataKey::from_str(k.as_str()).map_err(|e| e.to_string())?,
@@ -278,6 +490,68 @@ fn decorate_req<T>(metadata: HashMap<String, String>, req: &mut Request<T>) -> R
     Ok(())
 }
 
+fn metadata_map(metadata: HashMap<String, String>) -> Result<MetadataMap, String> {
+    let mut req = Request::new(());
+    decorate_req(metadata, &mut req)?;
+    Ok(req.into_parts().0)
+}
+
+fn new_request<T>(message: T, metadata: &MetadataMap, options: &CallOptions) -> Request<T> {
+    let mut req = Request::new(message);
+    *req.metadata_mut() = metadata.clone();
+    if let Some(deadline) = options.deadline {
+        req.set_timeout(deadline);
+    }
+    req
+}
+
+/// Make the call, and if it should wait for the server to be ready, make it again with
+/// backoff for as long as the server is unavailable. Calls with a deadline wait until it
+/// passes, and calls without one wait up to [`MAX_READY_WAIT`].
+async fn retry_unavailable<T, F>(
+    options: &CallOptions,
+    can_retry: impl Fn() -> bool,
+    mut call: impl FnMut() -> F,
+) -> Result<T, Status>
+where
+    F: Future<Output = Result<T, Status>>,
+{
+    let give_up = match options.deadline {
+        Some(_) => None,
+        None => Some(Instant::now() + MAX_READY_WAIT),
+    };
+    let mut backoff = INITIAL_READY_BACKOFF;
+    loop {
+        let status = match call().await {
+            Err(s) if s.code() == Code::Unavailable => s,
+            result => return result,
+        };
+        let out_of_time = give_up.is_some_and(|t| Instant::now() + backoff > t);
+        if !options.wait_for_ready || !can_retry() || out_of_time {
+            return Err(status);
+        }
+        debug!(
+            "Retrying in {backoff:?}, server unavailable: {}",
+            status.message()
+        );
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_READY_BACKOFF);
+    }
+}
+
+/// Fail the call with `DeadlineExceeded` if it doesn't finish before the deadline
+async fn with_deadline<T>(
+    options: &CallOptions,
+    call: impl Future<Output = Result<T, Status>>,
+) -> Result<T, Status> {
+    match options.deadline {
+        Some(deadline) => tokio::time::timeout(deadline, call)
+            .await
+            .map_err(|_| Status::deadline_exceeded("Deadline exceeded"))?,
+        None => call.await,
+    }
+}
+
 fn uri_from_str(uri_str: &str) -> Result<Uri, String> {
     match Uri::from_str(uri_str) {
         Ok(uri) => Ok(uri),
@@ -288,17 +562,105 @@ fn uri_from_str(uri_str: &str) -> Result<Uri, String> {
     }
 }
 
-fn make_pool_key(id: &str, uri: &str, proto_files: &Vec<PathBuf>) -> String {
-    let pool_key = format!(
-        "{}::{}::{}",
-        id,
-        uri,
-        proto_files
+fn make_pool_key(
+    id: &str,
+    uri: &str,
+    proto_files: &Vec<PathBuf>,
+    proto_includes: &Vec<PathBuf>,
+) -> String {
+    let join_paths = |paths: &Vec<PathBuf>| {
+        paths
             .iter()
             .map(|p| p.to_string_lossy().to_string())
             .collect::<Vec<String>>()
             .join(":")
+    };
+    let pool_key = format!(
+        "{}::{}::{}::{}",
+        id,
+        uri,
+        join_paths(proto_files),
+        join_paths(proto_includes)
     );
 
     format!("{:x}", md5::compute(pool_key))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use tonic::{Code, Status};
+
+    use crate::manager::{retry_unavailable, with_deadline};
+    use crate::CallOptions;
+
+    #[tokio::test]
+    async fn deadline() {
+        let options = CallOptions {
+            deadline: Some(Duration::from_millis(50)),
+            ..Default::default()
+        };
+        let slow = async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok(())
+        };
+        let result = with_deadline(&options, slow).await;
+        assert_eq!(result.unwrap_err().code(), Code::DeadlineExceeded);
+
+        let result = with_deadline(&options, async { Ok(1) }).await;
+        assert_eq!(result.unwrap(), 1);
+
+        let result = with_deadline(&CallOptions::default(), async { Ok(1) }).await;
+        assert_eq!(result.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn retry_until_available() {
+        let attempts = AtomicUsize::new(0);
+        let call = || async {
+            match attempts.fetch_add(1, Ordering::SeqCst) {
+                0 | 1 => Err(Status::unavailable("connection refused")),
+                _ => Ok("done"),
+            }
+        };
+
+        // Without waiting for ready, the first failure is returned
+        let result = retry_unavailable(&CallOptions::default(), || true, call).await;
+        assert_eq!(result.unwrap_err().code(), Code::Unavailable);
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+
+        attempts.store(0, Ordering::SeqCst);
+        let options = CallOptions {
+            wait_for_ready: true,
+            ..Default::default()
+        };
+        let result = retry_unavailable(&options, || true, call).await;
+        assert_eq!(result.unwrap(), "done");
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+        // Calls that already sent messages can't be made again
+        attempts.store(0, Ordering::SeqCst);
+        let result = retry_unavailable(&options, || false, call).await;
+        assert_eq!(result.unwrap_err().code(), Code::Unavailable);
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn retry_bounded_by_deadline() {
+        let options = CallOptions {
+            deadline: Some(Duration::from_millis(300)),
+            wait_for_ready: true,
+            ..Default::default()
+        };
+        let call = || async { Err::<(), _>(Status::unavailable("connection refused")) };
+        let result = with_deadline(&options, retry_unavailable(&options, || true, call)).await;
+        assert_eq!(result.unwrap_err().code(), Code::DeadlineExceeded);
+
+        // Other errors aren't retried
+        let call = || async { Err::<(), _>(Status::internal("boom")) };
+        let result = retry_unavailable(&options, || true, call).await;
+        assert_eq!(result.unwrap_err().code(), Code::Internal);
+    }
+}
@@ -1,136 +1,415 @@
-use std::env::temp_dir;
+use std::collections::HashMap;
+use std::fs::File;
+use std::future::Future;
+use std::io::BufReader;
 use std::ops::Deref;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::SystemTime;
 
 use anyhow::anyhow;
+use hyper::client::connect::{Connected, Connection};
 use hyper::client::HttpConnector;
+use hyper::service::Service;
 use hyper::Client;
 use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
 use log::{debug, warn};
+use miette::Diagnostic;
 use prost::Message;
 use prost_reflect::{DescriptorPool, MethodDescriptor};
 use prost_types::{FileDescriptorProto, FileDescriptorSet};
-use tauri::path::BaseDirectory;
-use tauri::{AppHandle, Manager};
-use tauri_plugin_shell::ShellExt;
+use rustls::client::{ServerCertVerified, ServerCertVerifier};
+use rustls::{Certificate, ClientConfig, PrivateKey, RootCertStore, ServerName};
 use tokio::fs;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
 use tokio_stream::StreamExt;
 use tonic::body::BoxBody;
+use tonic::client::Grpc;
+use tonic::codec::ProstCodec;
 use tonic::codegen::http::uri::PathAndQuery;
 use tonic::transport::Uri;
-use tonic::Request;
-use tonic_reflection::pb::server_reflection_client::ServerReflectionClient;
+use tonic::{Code, Request, Status};
 use tonic_reflection::pb::server_reflection_request::MessageRequest;
 use tonic_reflection::pb::server_reflection_response::MessageResponse;
-use tonic_reflection::pb::ServerReflectionRequest;
+use tonic_reflection::pb::{ServerReflectionRequest, ServerReflectionResponse};
+
+use crate::channel::GrpcChannel;
+use crate::manager::decorate_req;
+use crate::{ChannelConfig, ReflectionVersion, TlsConfig};
+
+pub type GrpcTransport = Client<HttpsConnector<GrpcConnector>, BoxBody>;
 
 pub async fn fill_pool_from_files(
-    app_handle: &AppHandle,
     paths: &Vec<PathBuf>,
+    include_paths: &Vec<PathBuf>,
 ) -> Result<DescriptorPool, String> {
     let mut pool = DescriptorPool::new();
-    let random_file_name = format!("{}.desc", uuid::Uuid::new_v4());
-    let desc_path = temp_dir().join(random_file_name);
-    let global_import_dir = app_handle
-        .path()
-        .resolve("protoc-include", BaseDirectory::Resource)
-        .expect("failed to resolve protoc include directory");
-
-    // HACK: Remove UNC prefix for Windows paths
-    let global_import_dir = dunce::simplified(global_import_dir.as_path())
-        .to_string_lossy()
-        .to_string();
-    let desc_path = dunce::simplified(desc_path.as_path());
-
-    let mut args = vec![
-        "--include_imports".to_string(),
-        "--include_source_info".to_string(),
-        "-I".to_string(),
-        global_import_dir,
-        "-o".to_string(),
-        desc_path.to_string_lossy().to_string(),
-    ];
+    // The compiler provides Google's well-known types itself, so only the user's includes
+    // are needed
+    let mut includes = include_paths.clone();
 
+    let mut proto_files = Vec::new();
     for p in paths {
-        if p.as_path().exists() {
-            args.push(p.to_string_lossy().to_string());
-        } else {
+        if !p.as_path().exists() {
+            debug!("ignoring {:?} since it does not exist.", p);
             continue;
         }
 
-        let parent = p.as_path().parent();
-        if let Some(parent_path) = parent {
-            args.push("-I".to_string());
-            args.push(parent_path.to_string_lossy().to_string());
-            args.push("-I".to_string());
-            args.push(parent_path.parent().unwrap().to_string_lossy().to_string());
-        } else {
-            debug!("ignoring {:?} since it does not exist.", parent)
+        // Precompiled descriptor sets are added as-is
+        if is_descriptor_set(p) {
+            let bytes = fs::read(p).await.map_err(|e| e.to_string())?;
+            let fds = FileDescriptorSet::decode(bytes.deref())
+                .map_err(|e| format!("Failed to decode {}: {e}", p.to_string_lossy()))?;
+            pool.add_file_descriptor_set(fds)
+                .map_err(|e| e.to_string())?;
+            continue;
         }
-    }
 
-    let out = app_handle
-        .shell()
-        .sidecar("yaakprotoc")
-        .expect("yaakprotoc not found")
-        .args(args)
-        .output()
-        .await
-        .expect("yaakprotoc failed to run");
-
-    if !out.status.success() {
-        return Err(format!(
-            "protoc failed with status {}: {}",
-            out.status.code().unwrap(),
-            String::from_utf8_lossy(out.stderr.as_slice())
-        ));
+        proto_files.push(p.clone());
+        if let Some(parent) = p.parent() {
+            includes.push(parent.to_path_buf());
+            if let Some(grandparent) = parent.parent() {
+                includes.push(grandparent.to_path_buf());
+            }
+        }
     }
 
-    let bytes = fs::read(desc_path).await.map_err(|e| e.to_string())?;
-    let fdp = FileDescriptorSet::decode(bytes.deref()).map_err(|e| e.to_string())?;
-    pool.add_file_descriptor_set(fdp)
-        .map_err(|e| e.to_string())?;
+    if proto_files.is_empty() {
+        return Ok(pool);
+    }
 
-    fs::remove_file(desc_path)
-        .await
+    // Skip include paths that don't exist, since the compiler fails on them
+    includes.retain(|p| p.is_dir());
+    let mut compiler = protox::Compiler::new(includes).map_err(compile_error)?;
+    compiler.include_imports(true).include_source_info(true);
+    compiler.open_files(proto_files).map_err(compile_error)?;
+    pool.add_file_descriptor_set(compiler.file_descriptor_set())
         .map_err(|e| e.to_string())?;
 
     Ok(pool)
 }
 
-pub async fn fill_pool_from_reflection(uri: &Uri) -> Result<DescriptorPool, String> {
+fn is_descriptor_set(path: &Path) -> bool {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) => ext == "protoset" || ext == "binpb",
+        None => false,
+    }
+}
+
+/// Describe a compile error, along with the file, line, and column it happened at
+fn compile_error(e: protox::Error) -> String {
+    let location = e
+        .labels()
+        .and_then(|mut labels| labels.next())
+        .and_then(|label| {
+            let contents = e.source_code()?.read_span(label.inner(), 0, 0).ok()?;
+            Some((contents.line() + 1, contents.column() + 1))
+        });
+    match (e.file(), location) {
+        (Some(file), Some((line, column))) => format!("{file}:{line}:{column}: {e}"),
+        (Some(file), None) => format!("{file}: {e}"),
+        _ => e.to_string(),
+    }
+}
+
+pub async fn fill_pool_from_reflection(
+    channel: GrpcChannel,
+    origin: Uri,
+    metadata: &HashMap<String, String>,
+) -> Result<(DescriptorPool, ReflectionVersion), String> {
     let mut pool = DescriptorPool::new();
-    let mut client = ServerReflectionClient::with_origin(get_transport(), uri.clone());
+    let mut client = ReflectionClient {
+        grpc: Grpc::with_origin(channel, origin),
+        version: ReflectionVersion::V1,
+        metadata: metadata.clone(),
+    };
 
-    for service in list_services(&mut client).await? {
-        if service == "grpc.reflection.v1alpha.ServerReflection" {
+    // Servers that only implement the older version respond to v1 with Unimplemented
+    let services = match list_services(&mut client).await {
+        Err(e) if e.code() == Code::Unimplemented => {
+            debug!("Reflection v1 is unimplemented, falling back to v1alpha");
+            client.version = ReflectionVersion::V1alpha;
+            list_services(&mut client).await
+        }
+        r => r,
+    }
+    .map_err(reflection_error)?;
+
+    for service in services {
+        if service == reflection_service_name(ReflectionVersion::V1)
+            || service == reflection_service_name(ReflectionVersion::V1alpha)
+        {
             continue;
         }
         file_descriptor_set_from_service_name(&service, &mut pool, &mut client).await;
     }
 
-    Ok(pool)
+    Ok((pool, client.version))
+}
+
+/// Sends reflection requests, with the request's metadata, to whichever version of the
+/// reflection service the server implements. Both versions use the same messages.
+struct ReflectionClient {
+    grpc: Grpc<GrpcChannel>,
+    version: ReflectionVersion,
+    metadata: HashMap<String, String>,
+}
+
+fn reflection_service_name(version: ReflectionVersion) -> &'static str {
+    match version {
+        ReflectionVersion::V1 => "grpc.reflection.v1.ServerReflection",
+        ReflectionVersion::V1alpha => "grpc.reflection.v1alpha.ServerReflection",
+    }
 }
 
-pub fn get_transport() -> Client<HttpsConnector<HttpConnector>, BoxBody> {
-    let connector = HttpsConnectorBuilder::new().with_native_roots();
-    let connector = connector.https_or_http().enable_http2().wrap_connector({
-        let mut http_connector = HttpConnector::new();
-        http_connector.enforce_http(false);
-        http_connector
-    });
-    Client::builder()
-        .pool_max_idle_per_host(0)
+pub fn get_transport(
+    uri: &Uri,
+    config: &ChannelConfig,
+    open_connections: Arc<AtomicUsize>,
+) -> Result<GrpcTransport, String> {
+    let mut builder = Client::builder();
+    builder
         .http2_only(true)
-        .build(connector)
+        .http2_keep_alive_interval(config.keepalive_interval)
+        .http2_keep_alive_while_idle(true);
+    if let Some(timeout) = config.keepalive_timeout {
+        builder.http2_keep_alive_timeout(timeout);
+    }
+    if let Some(timeout) = config.idle_timeout {
+        builder.pool_idle_timeout(timeout);
+    }
+    let connector = https_connector(uri, &config.tls, true, open_connections)?;
+    Ok(builder.build(connector))
 }
 
-async fn list_services(
-    reflect_client: &mut ServerReflectionClient<Client<HttpsConnector<HttpConnector>, BoxBody>>,
-) -> Result<Vec<String>, String> {
+pub(crate) fn https_connector(
+    uri: &Uri,
+    tls: &TlsConfig,
+    http2: bool,
+    open_connections: Arc<AtomicUsize>,
+) -> Result<HttpsConnector<GrpcConnector>, String> {
+    let connector = HttpsConnectorBuilder::new()
+        .with_tls_config(tls_client_config(tls)?)
+        .https_or_http();
+    let connector = match non_empty(&tls.server_name) {
+        Some(name) => connector.with_server_name(name.to_string()),
+        None => connector,
+    };
+    let mut http = HttpConnector::new();
+    http.enforce_http(false);
+    let http = GrpcConnector {
+        http,
+        // Requests are addressed to the overridden authority, so connect to the URL instead
+        target: non_empty(&tls.authority).map(|_| uri.clone()),
+        open_connections,
+    };
+    Ok(match http2 {
+        true => connector.enable_http2().wrap_connector(http),
+        false => connector.enable_http1().wrap_connector(http),
+    })
+}
+
+/// The origin requests are addressed to, which sets their `:authority`
+pub fn request_origin(uri: &Uri, tls: &TlsConfig) -> Result<Uri, String> {
+    let authority = match non_empty(&tls.authority) {
+        Some(a) => a,
+        None => return Ok(uri.clone()),
+    };
+    let scheme = uri.scheme_str().unwrap_or("http");
+    Uri::from_str(&format!("{scheme}://{authority}"))
+        .map_err(|e| format!("Invalid authority {authority}: {e}"))
+}
+
+/// Connects to the target if there is one, instead of the address in the request URI, and
+/// counts the connections that are open
+#[derive(Clone)]
+pub struct GrpcConnector {
+    http: HttpConnector,
+    target: Option<Uri>,
+    open_connections: Arc<AtomicUsize>,
+}
+
+impl Service<Uri> for GrpcConnector {
+    type Response = TrackedStream;
+    type Error = <HttpConnector as Service<Uri>>::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<TrackedStream, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.http.poll_ready(cx)
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let connecting = self.http.call(self.target.clone().unwrap_or(uri));
+        let open_connections = self.open_connections.clone();
+        Box::pin(async move { Ok(TrackedStream::new(connecting.await?, open_connections)) })
+    }
+}
+
+/// A connection that's counted as open until hyper drops it, which happens once the server
+/// closes it or it has been idle for too long
+pub struct TrackedStream {
+    inner: TcpStream,
+    open_connections: Arc<AtomicUsize>,
+}
+
+impl TrackedStream {
+    fn new(inner: TcpStream, open_connections: Arc<AtomicUsize>) -> Self {
+        open_connections.fetch_add(1, Ordering::SeqCst);
+        TrackedStream {
+            inner,
+            open_connections,
+        }
+    }
+}
+
+impl Drop for TrackedStream {
+    fn drop(&mut self) {
+        self.open_connections.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Connection for TrackedStream {
+    fn connected(&self) -> Connected {
+        self.inner.connected()
+    }
+}
+
+impl AsyncRead for TrackedStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for TrackedStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[std::io::IoSlice<'_>],
+    ) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+fn tls_client_config(tls: &TlsConfig) -> Result<ClientConfig, String> {
+    let mut roots = RootCertStore::empty();
+    match rustls_native_certs::load_native_certs() {
+        Ok(certs) => {
+            for cert in certs {
+                if let Err(e) = roots.add(&Certificate(cert.0)) {
+                    debug!("Skipping invalid native certificate: {}", e);
+                }
+            }
+        }
+        Err(e) => warn!("Failed to load native certificates: {}", e),
+    }
+    for path in &tls.ca_cert_paths {
+        for cert in read_pem_certs(path)? {
+            roots
+                .add(&cert)
+                .map_err(|e| format!("Invalid CA certificate in {path}: {e}"))?;
+        }
+    }
+
+    let builder = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots);
+    let mut config = match (
+        non_empty(&tls.client_cert_path),
+        non_empty(&tls.client_key_path),
+    ) {
+        (Some(cert_path), Some(key_path)) => builder
+            .with_client_auth_cert(read_pem_certs(cert_path)?, read_pem_key(key_path)?)
+            .map_err(|e| format!("Invalid client certificate: {e}"))?,
+        (None, None) => builder.with_no_client_auth(),
+        _ => return Err("Client certificate and key must be set together".to_string()),
+    };
+
+    if tls.skip_verify {
+        config
+            .dangerous()
+            .set_certificate_verifier(Arc::new(SkipServerVerification));
+    }
+
+    Ok(config)
+}
+
+fn non_empty(v: &Option<String>) -> Option<&str> {
+    v.as_deref().filter(|s| !s.is_empty())
+}
+
+fn read_pem_certs(path: &str) -> Result<Vec<Certificate>, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {path}: {e}"))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .map_err(|e| format!("Failed to read certificates from {path}: {e}"))?;
+    if certs.is_empty() {
+        return Err(format!("No certificates found in {path}"));
+    }
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+fn read_pem_key(path: &str) -> Result<PrivateKey, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {path}: {e}"))?;
+    let items = rustls_pemfile::read_all(&mut BufReader::new(file))
+        .map_err(|e| format!("Failed to read private key from {path}: {e}"))?;
+    items
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::ECKey(key) => Some(PrivateKey(key)),
+            _ => None,
+        })
+        .ok_or(format!("No private key found in {path}"))
+}
+
+struct SkipServerVerification;
+
+impl ServerCertVerifier for SkipServerVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+async fn list_services(reflect_client: &mut ReflectionClient) -> Result<Vec<String>, Status> {
     let response =
-        send_reflection_request(reflect_client, MessageRequest::ListServices("".into())).await?;
+        server_reflection_info(reflect_client, MessageRequest::ListServices("".into())).await?;
 
     let list_services_response = match response {
         MessageResponse::ListServicesResponse(resp) => resp,
@@ -147,7 +426,7 @@ async fn list_services(
 async fn file_descriptor_set_from_service_name(
     service_name: &str,
     pool: &mut DescriptorPool,
-    client: &mut ServerReflectionClient<Client<HttpsConnector<HttpConnector>, BoxBody>>,
+    client: &mut ReflectionClient,
 ) {
     let response = match send_reflection_request(
         client,
@@ -186,7 +465,7 @@ async fn file_descriptor_set_from_service_name(
 async fn file_descriptor_set_by_filename(
     filename: &str,
     pool: &mut DescriptorPool,
-    client: &mut ServerReflectionClient<Client<HttpsConnector<HttpConnector>, BoxBody>>,
+    client: &mut ReflectionClient,
 ) {
     // We already fetched this file
     if let Some(_) = pool.get_file_by_name(filename) {
@@ -214,32 +493,56 @@ async fn file_descriptor_set_by_filename(
 }
 
 async fn send_reflection_request(
-    client: &mut ServerReflectionClient<Client<HttpsConnector<HttpConnector>, BoxBody>>,
+    client: &mut ReflectionClient,
     message: MessageRequest,
 ) -> Result<MessageResponse, String> {
+    server_reflection_info(client, message)
+        .await
+        .map_err(reflection_error)
+}
+
+async fn server_reflection_info(
+    client: &mut ReflectionClient,
+    message: MessageRequest,
+) -> Result<MessageResponse, Status> {
     let reflection_request = ServerReflectionRequest {
         host: "".into(), // Doesn't matter
         message_request: Some(message),
     };
 
-    let request = Request::new(tokio_stream::once(reflection_request));
+    let mut request = Request::new(tokio_stream::once(reflection_request));
+    decorate_req(client.metadata.clone(), &mut request).map_err(Status::invalid_argument)?;
 
+    let path = format!(
+        "/{}/ServerReflectionInfo",
+        reflection_service_name(client.version)
+    );
+    let codec: ProstCodec<ServerReflectionRequest, ServerReflectionResponse> =
+        ProstCodec::default();
     client
-        .server_reflection_info(request)
+        .grpc
+        .ready()
         .await
-        .map_err(|e| match e.code() {
-            tonic::Code::Unavailable => "Failed to connect to endpoint".to_string(),
-            tonic::Code::Unauthenticated => "Authentication failed".to_string(),
-            tonic::Code::DeadlineExceeded => "Deadline exceeded".to_string(),
-            _ => e.to_string(),
-        })?
+        .map_err(|e| Status::unavailable(e.to_string()))?;
+    client
+        .grpc
+        .streaming(request, PathAndQuery::from_str(&path).unwrap(), codec)
+        .await?
         .into_inner()
         .next()
         .await
-        .expect("steamed response")
-        .map_err(|e| e.to_string())?
+        .ok_or(Status::unknown("No reflection response"))??
         .message_response
-        .ok_or("No reflection response".to_string())
+        .ok_or(Status::unknown("No reflection response"))
+}
+
+fn reflection_error(e: Status) -> String {
+    match e.code() {
+        Code::Unavailable => "Failed to connect to endpoint".to_string(),
+        Code::Unauthenticated => "Authentication failed".to_string(),
+        Code::DeadlineExceeded => "Deadline exceeded".to_string(),
+        _ => e.to_string(),
+    }
 }
 
 pub fn method_desc_to_path(md: &MethodDescriptor) -> PathAndQuery {
@@ -250,3 +553,73 @@ pub fn method_desc_to_path(md: &MethodDescriptor) -> PathAndQuery {
         .expect("invalid method path");
     PathAndQuery::from_str(&format!("/{}/{}", namespace, method_name)).expect("invalid method path")
 }
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use tonic::transport::Uri;
+
+    use crate::proto::{request_origin, tls_client_config};
+    use crate::TlsConfig;
+
+    #[test]
+    fn origin_with_authority() {
+        let uri = Uri::from_str("https://10.0.0.1:8443/pkg.Service").unwrap();
+        let tls = TlsConfig {
+            authority: Some("api.example.com".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            request_origin(&uri, &tls).unwrap(),
+            Uri::from_str("https://api.example.com").unwrap()
+        );
+
+        // An empty authority is the same as none
+        let tls = TlsConfig {
+            authority: Some("".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(request_origin(&uri, &tls).unwrap(), uri);
+
+        let tls = TlsConfig {
+            authority: Some("bad authority".to_string()),
+            ..Default::default()
+        };
+        assert!(request_origin(&uri, &tls).is_err());
+    }
+
+    #[test]
+    fn client_cert_pairing() {
+        assert!(tls_client_config(&TlsConfig::default()).is_ok());
+
+        let tls = TlsConfig {
+            client_cert_path: Some("client.pem".to_string()),
+            client_key_path: Some("".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            tls_client_config(&tls).err(),
+            Some("Client certificate and key must be set together".to_string())
+        );
+
+        let tls = TlsConfig {
+            client_key_path: Some("client.key".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            tls_client_config(&tls).err(),
+            Some("Client certificate and key must be set together".to_string())
+        );
+    }
+
+    #[test]
+    fn missing_ca_cert() {
+        let tls = TlsConfig {
+            ca_cert_paths: vec!["/nonexistent/ca.pem".to_string()],
+            ..Default::default()
+        };
+        let err = tls_client_config(&tls).err().unwrap();
+        assert!(err.starts_with("Failed to open /nonexistent/ca.pem"));
+    }
+}
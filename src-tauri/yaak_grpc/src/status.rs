@@ -0,0 +1,157 @@
+use std::sync::OnceLock;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use log::warn;
+use prost::Message;
+use prost_reflect::{DescriptorPool, DynamicMessage};
+use prost_types::Any;
+use protox::file::{ChainFileResolver, File, FileResolver, GoogleFileResolver};
+use serde_json::{json, Value};
+use tonic::Status;
+
+use crate::SERIALIZE_OPTIONS;
+
+const ERROR_DETAILS_FILE: &str = "google/rpc/error_details.proto";
+const ERROR_DETAILS_SOURCE: &str = include_str!("../protos/google/rpc/error_details.proto");
+
+static ERROR_DETAILS_POOL: OnceLock<DescriptorPool> = OnceLock::new();
+
+/// The `google.rpc.Status` servers send in `grpc-status-details-bin`
+#[derive(Clone, PartialEq, Message)]
+struct RpcStatus {
+    #[prost(int32, tag = "1")]
+    code: i32,
+    #[prost(string, tag = "2")]
+    message: String,
+    #[prost(message, repeated, tag = "3")]
+    details: Vec<Any>,
+}
+
+/// Decode the details of a failed call's status into JSON, like `Any` messages are. Details
+/// that aren't standard error details, like `BadRequest` or `RetryInfo`, are looked up in
+/// the pool, and their bytes are kept base64 encoded if they can't be found there either.
+pub fn decode_status_details(status: &Status, pool: &DescriptorPool) -> Vec<Value> {
+    if status.details().is_empty() {
+        return Vec::new();
+    }
+
+    let rpc_status = match RpcStatus::decode(status.details()) {
+        Ok(s) => s,
+        Err(e) => {
+            warn!("Failed to decode status details: {}", e);
+            return Vec::new();
+        }
+    };
+
+    rpc_status
+        .details
+        .iter()
+        .map(|any| decode_any(any, pool))
+        .collect()
+}
+
+fn decode_any(any: &Any, pool: &DescriptorPool) -> Value {
+    // Type URLs look like `type.googleapis.com/google.rpc.BadRequest`
+    let name = any.type_url.rsplit('/').next().unwrap_or_default();
+    let desc = error_details_pool()
+        .get_message_by_name(name)
+        .or_else(|| pool.get_message_by_name(name));
+
+    let fields = match desc.map(|d| DynamicMessage::decode(d, any.value.as_slice())) {
+        Some(Ok(msg)) => msg
+            .serialize_with_options(serde_json::value::Serializer, SERIALIZE_OPTIONS)
+            .ok(),
+        _ => None,
+    };
+
+    match fields {
+        Some(Value::Object(mut fields)) => {
+            fields.insert("@type".to_string(), any.type_url.clone().into());
+            Value::Object(fields)
+        }
+        _ => json!({ "@type": any.type_url, "value": STANDARD.encode(&any.value) }),
+    }
+}
+
+/// Descriptors of the standard error details, compiled the first time they're needed
+fn error_details_pool() -> &'static DescriptorPool {
+    ERROR_DETAILS_POOL.get_or_init(|| {
+        let mut resolver = ChainFileResolver::new();
+        resolver.add(ErrorDetailsResolver);
+        resolver.add(GoogleFileResolver::new());
+
+        let mut compiler = protox::Compiler::with_file_resolver(resolver);
+        compiler.include_imports(true);
+        compiler
+            .open_file(ERROR_DETAILS_FILE)
+            .expect("error details to compile");
+        DescriptorPool::from_file_descriptor_set(compiler.file_descriptor_set())
+            .expect("error details to be valid descriptors")
+    })
+}
+
+struct ErrorDetailsResolver;
+
+impl FileResolver for ErrorDetailsResolver {
+    fn open_file(&self, name: &str) -> Result<File, protox::Error> {
+        match name {
+            ERROR_DETAILS_FILE => File::from_source(name, ERROR_DETAILS_SOURCE),
+            _ => Err(protox::Error::file_not_found(name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use prost::Message;
+    use prost_reflect::{DescriptorPool, DynamicMessage};
+    use prost_types::Any;
+    use serde_json::json;
+    use tonic::{Code, Status};
+
+    use crate::status::{decode_status_details, error_details_pool, RpcStatus};
+
+    #[test]
+    fn decode_details() {
+        let desc = error_details_pool()
+            .get_message_by_name("google.rpc.BadRequest")
+            .unwrap();
+        let bad_request = DynamicMessage::deserialize(
+            desc,
+            json!({ "fieldViolations": [{ "field": "name", "description": "required" }] }),
+        )
+        .unwrap();
+
+        let details = RpcStatus {
+            code: Code::InvalidArgument as i32,
+            message: "invalid name".to_string(),
+            details: vec![
+                Any {
+                    type_url: "type.googleapis.com/google.rpc.BadRequest".to_string(),
+                    value: bad_request.encode_to_vec(),
+                },
+                Any {
+                    type_url: "type.googleapis.com/acme.Unknown".to_string(),
+                    value: vec![1, 2, 3],
+                },
+            ],
+        };
+        let status = Status::with_details(
+            Code::InvalidArgument,
+            "invalid name",
+            details.encode_to_vec().into(),
+        );
+
+        assert_eq!(
+            decode_status_details(&status, &DescriptorPool::new()),
+            vec![
+                json!({
+                    "@type": "type.googleapis.com/google.rpc.BadRequest",
+                    "fieldViolations": [{ "field": "name", "description": "required" }],
+                }),
+                json!({ "@type": "type.googleapis.com/acme.Unknown", "value": "AQID" }),
+            ]
+        );
+    }
+}
@@ -0,0 +1,253 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use bytes::{Bytes, BytesMut};
+use hyper::body::{HttpBody, Sender};
+use hyper::header::{HeaderName, HeaderValue, ACCEPT, CONTENT_TYPE, TE};
+use hyper::service::Service;
+use hyper::{Body, Client, HeaderMap, Request, Response, Version};
+use hyper_rustls::HttpsConnector;
+use log::warn;
+use tonic::body::BoxBody;
+
+use crate::proto::GrpcConnector;
+
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+const TRAILERS_FLAG: u8 = 0x80;
+
+/// Sends calls as gRPC-Web over HTTP/1.1, and translates the responses back into regular
+/// gRPC responses, so tonic can handle them as usual
+#[derive(Clone)]
+pub struct GrpcWebClient {
+    client: Client<HttpsConnector<GrpcConnector>, Body>,
+    /// Whether bodies are base64 encoded (`grpc-web-text`)
+    text: bool,
+}
+
+impl GrpcWebClient {
+    pub fn new(client: Client<HttpsConnector<GrpcConnector>, Body>, text: bool) -> Self {
+        GrpcWebClient { client, text }
+    }
+}
+
+impl Service<Request<BoxBody>> for GrpcWebClient {
+    type Response = Response<Body>;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<BoxBody>) -> Self::Future {
+        let client = self.client.clone();
+        let text = self.text;
+        Box::pin(async move {
+            let (mut parts, body) = req.into_parts();
+
+            // gRPC-Web can't stream requests, so the whole body is sent at once
+            let body = hyper::body::to_bytes(body).await?;
+            let body = match text {
+                true => Bytes::from(STANDARD.encode(&body)),
+                false => body,
+            };
+
+            let content_type = HeaderValue::from_static(match text {
+                true => "application/grpc-web-text+proto",
+                false => "application/grpc-web+proto",
+            });
+            parts.headers.insert(CONTENT_TYPE, content_type.clone());
+            parts.headers.insert(ACCEPT, content_type);
+            parts
+                .headers
+                .insert("x-grpc-web", HeaderValue::from_static("1"));
+            parts.headers.remove(TE);
+            parts.version = Version::HTTP_11;
+
+            let response = client
+                .request(Request::from_parts(parts, Body::from(body)))
+                .await?;
+
+            let (mut parts, body) = response.into_parts();
+            parts
+                .headers
+                .insert(CONTENT_TYPE, HeaderValue::from_static("application/grpc"));
+            let (sender, translated) = Body::channel();
+            tokio::spawn(translate_body(body, sender, text));
+            Ok(Response::from_parts(parts, translated))
+        })
+    }
+}
+
+/// Forward the messages in a gRPC-Web body, and send its trailer frame as HTTP trailers
+async fn translate_body(mut body: Body, mut sender: Sender, text: bool) {
+    let mut decoder = WebDecoder::new(text);
+    while let Some(chunk) = body.data().await {
+        let frames = match chunk.map_err(|e| e.to_string()) {
+            Ok(chunk) => decoder.push(&chunk),
+            Err(e) => Err(e),
+        };
+        let frames = match frames {
+            Ok(frames) => frames,
+            Err(e) => {
+                warn!("Failed to read gRPC-Web response: {}", e);
+                sender.abort();
+                return;
+            }
+        };
+
+        for frame in frames {
+            match frame {
+                WebFrame::Data(data) => {
+                    if sender.send_data(data).await.is_err() {
+                        return;
+                    }
+                }
+                WebFrame::Trailers(trailers) => {
+                    let _ = sender.send_trailers(trailers).await;
+                    return;
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum WebFrame {
+    /// A length-prefixed message, as regular gRPC expects it
+    Data(Bytes),
+    Trailers(HeaderMap),
+}
+
+struct WebDecoder {
+    text: bool,
+    encoded: BytesMut,
+    buf: BytesMut,
+}
+
+impl WebDecoder {
+    fn new(text: bool) -> Self {
+        WebDecoder {
+            text,
+            encoded: BytesMut::new(),
+            buf: BytesMut::new(),
+        }
+    }
+
+    /// Add a chunk of the body, returning any frames that are now complete
+    fn push(&mut self, chunk: &[u8]) -> Result<Vec<WebFrame>, String> {
+        if self.text {
+            let chunk: Vec<u8> = chunk
+                .iter()
+                .copied()
+                .filter(|b| !b.is_ascii_whitespace())
+                .collect();
+            self.encoded.extend_from_slice(&chunk);
+
+            // Each frame can be encoded and padded separately, so decode in groups of 4
+            let len = self.encoded.len() - self.encoded.len() % 4;
+            let quads = self.encoded.split_to(len);
+            for quad in quads.chunks(4) {
+                let decoded = STANDARD.decode(quad).map_err(|e| e.to_string())?;
+                self.buf.extend_from_slice(&decoded);
+            }
+        } else {
+            self.buf.extend_from_slice(chunk);
+        }
+
+        let mut frames = Vec::new();
+        while self.buf.len() >= 5 {
+            let len = u32::from_be_bytes([self.buf[1], self.buf[2], self.buf[3], self.buf[4]]);
+            let len = len as usize + 5;
+            if self.buf.len() < len {
+                break;
+            }
+
+            let frame = self.buf.split_to(len).freeze();
+            if frame[0] & TRAILERS_FLAG != 0 {
+                frames.push(WebFrame::Trailers(parse_trailers(&frame[5..])));
+            } else {
+                frames.push(WebFrame::Data(frame));
+            }
+        }
+
+        Ok(frames)
+    }
+}
+
+fn parse_trailers(block: &[u8]) -> HeaderMap {
+    let mut trailers = HeaderMap::new();
+    for line in String::from_utf8_lossy(block).split("\r\n") {
+        let (name, value) = match line.split_once(':') {
+            Some(v) => v,
+            None => continue,
+        };
+        let name = HeaderName::from_bytes(name.trim().to_lowercase().as_bytes());
+        match (name, HeaderValue::from_str(value.trim())) {
+            (Ok(name), Ok(value)) => {
+                trailers.append(name, value);
+            }
+            _ => warn!("Ignoring invalid gRPC-Web trailer {}", line),
+        }
+    }
+    trailers
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use bytes::Bytes;
+    use hyper::HeaderMap;
+
+    use crate::web::{WebDecoder, WebFrame};
+
+    fn frame(flag: u8, payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![flag];
+        frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    fn expected() -> Vec<WebFrame> {
+        let mut trailers = HeaderMap::new();
+        trailers.insert("grpc-status", "0".parse().unwrap());
+        trailers.insert("grpc-message", "OK".parse().unwrap());
+        vec![
+            WebFrame::Data(Bytes::from(frame(0, b"hello"))),
+            WebFrame::Trailers(trailers),
+        ]
+    }
+
+    #[test]
+    fn decode_binary() {
+        let mut body = frame(0, b"hello");
+        body.extend(frame(0x80, b"grpc-status:0\r\nGrpc-Message: OK\r\n"));
+
+        // Frames can be split across chunks
+        let mut decoder = WebDecoder::new(false);
+        let mut frames = decoder.push(&body[..3]).unwrap();
+        assert_eq!(frames, vec![]);
+        frames.extend(decoder.push(&body[3..]).unwrap());
+        assert_eq!(frames, expected());
+    }
+
+    #[test]
+    fn decode_text() {
+        // Each frame is encoded separately, so padding can appear mid-body
+        let body = format!(
+            "{}{}",
+            STANDARD.encode(frame(0, b"hello")),
+            STANDARD.encode(frame(0x80, b"grpc-status:0\r\ngrpc-message:OK")),
+        );
+
+        let mut decoder = WebDecoder::new(true);
+        let mut frames = decoder.push(&body.as_bytes()[..7]).unwrap();
+        frames.extend(decoder.push(&body.as_bytes()[7..]).unwrap());
+        assert_eq!(frames, expected());
+    }
+}
@@ -6,6 +6,10 @@ pub enum Error {
     SqlError(#[from] rusqlite::Error),
     #[error("JSON error: {0}")]
     JsonError(#[from] serde_json::Error),
+    #[error("Validation error: {0}")]
+    ValidationError(String),
+    #[error("Secrets error: {0}")]
+    SecretsError(String),
     #[error("unknown error")]
     Unknown,
 }
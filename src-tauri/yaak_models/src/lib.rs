@@ -1,5 +1,6 @@
 pub mod models;
 pub mod queries;
+pub mod secrets;
 mod error;
 
 pub mod plugin;
\ No newline at end of file
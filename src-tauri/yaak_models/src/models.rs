@@ -216,6 +216,10 @@ pub struct Environment {
     pub updated_at: NaiveDateTime,
     pub name: String,
     pub variables: Vec<EnvironmentVariable>,
+    /// Parent environment to inherit variables from
+    pub environment_id: Option<String>,
+    /// Paths of `.env` files whose variables are merged into this environment
+    pub env_files: Vec<String>,
 }
 
 #[derive(Iden)]
@@ -225,10 +229,12 @@ pub enum EnvironmentIden {
     Id,
     Model,
     WorkspaceId,
+    EnvironmentId,
     CreatedAt,
     UpdatedAt,
     Name,
     Variables,
+    EnvFiles,
 }
 
 impl<'s> TryFrom<&Row<'s>> for Environment {
@@ -236,6 +242,7 @@ impl<'s> TryFrom<&Row<'s>> for Environment {
 
     fn try_from(r: &Row<'s>) -> Result<Self, Self::Error> {
         let variables: String = r.get("variables")?;
+        let env_files: String = r.get("env_files")?;
         Ok(Environment {
             id: r.get("id")?,
             model: r.get("model")?,
@@ -244,6 +251,8 @@ impl<'s> TryFrom<&Row<'s>> for Environment {
             updated_at: r.get("updated_at")?,
             name: r.get("name")?,
             variables: serde_json::from_str(variables.as_str()).unwrap_or_default(),
+            environment_id: r.get("environment_id")?,
+            env_files: serde_json::from_str(env_files.as_str()).unwrap_or_default(),
         })
     }
 }
@@ -256,6 +265,23 @@ pub struct EnvironmentVariable {
     pub enabled: bool,
     pub name: String,
     pub value: String,
+    /// Secret values are encrypted at rest, masked in previews, and left out of exports
+    #[ts(optional, as = "Option<bool>")]
+    pub secret: bool,
+    /// What the value holds, which decides how it's validated and inlined into JSON bodies.
+    /// Untyped values are inlined as-is.
+    #[ts(optional)]
+    pub value_type: Option<EnvironmentVariableType>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq, TS)]
+#[serde(rename_all = "snake_case")]
+pub enum EnvironmentVariableType {
+    String,
+    Number,
+    Boolean,
+    Json,
+    Multiline,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default, TS)]
@@ -270,6 +296,7 @@ pub struct Folder {
     pub model: String,
     pub name: String,
     pub sort_priority: f32,
+    pub variables: Vec<EnvironmentVariable>,
 }
 
 #[derive(Iden)]
@@ -284,12 +311,14 @@ pub enum FolderIden {
     UpdatedAt,
     Name,
     SortPriority,
+    Variables,
 }
 
 impl<'s> TryFrom<&Row<'s>> for Folder {
     type Error = rusqlite::Error;
 
     fn try_from(r: &Row<'s>) -> Result<Self, Self::Error> {
+        let variables: String = r.get("variables")?;
         Ok(Folder {
             id: r.get("id")?,
             model: r.get("model")?,
@@ -299,6 +328,7 @@ impl<'s> TryFrom<&Row<'s>> for Folder {
             updated_at: r.get("updated_at")?,
             folder_id: r.get("folder_id")?,
             name: r.get("name")?,
+            variables: serde_json::from_str(variables.as_str()).unwrap_or_default(),
         })
     }
 }
@@ -346,6 +376,8 @@ pub struct HttpRequest {
     pub authentication: HashMap<String, Value>,
     pub authentication_type: Option<String>,
     pub headers: Vec<HttpRequestHeader>,
+    /// Variables only this request sees, which override all others
+    pub variables: Vec<EnvironmentVariable>,
 }
 
 #[derive(Iden)]
@@ -368,6 +400,7 @@ pub enum HttpRequestIden {
     Authentication,
     AuthenticationType,
     Headers,
+    Variables,
 }
 
 impl<'s> TryFrom<&Row<'s>> for HttpRequest {
@@ -378,6 +411,7 @@ impl<'s> TryFrom<&Row<'s>> for HttpRequest {
         let body: String = r.get("body")?;
         let authentication: String = r.get("authentication")?;
         let headers: String = r.get("headers")?;
+        let variables: String = r.get("variables")?;
         Ok(HttpRequest {
             id: r.get("id")?,
             model: r.get("model")?,
@@ -395,6 +429,7 @@ impl<'s> TryFrom<&Row<'s>> for HttpRequest {
             headers: serde_json::from_str(headers.as_str()).unwrap_or_default(),
             folder_id: r.get("folder_id")?,
             name: r.get("name")?,
+            variables: serde_json::from_str(variables.as_str()).unwrap_or_default(),
         })
     }
 }
@@ -518,6 +553,57 @@ pub struct GrpcRequest {
     #[ts(type = "Record<string, any>")]
     pub authentication: HashMap<String, Value>,
     pub metadata: Vec<GrpcMetadataEntry>,
+    pub tls: GrpcTlsConfig,
+    /// Native gRPC, or gRPC-Web with binary or base64 text bodies
+    #[ts(type = "\"grpc\" | \"grpc_web\" | \"grpc_web_text\"")]
+    pub protocol: String,
+    pub call_options: GrpcCallOptions,
+    pub channel_options: GrpcChannelOptions,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, TS)]
+#[serde(default, rename_all = "camelCase")]
+pub struct GrpcCallOptions {
+    /// Milliseconds the call may take, sent as `grpc-timeout`, or 0 for no deadline
+    pub deadline: i32,
+    /// Compress sent messages with gzip
+    pub send_gzip: bool,
+    /// Let the server compress its messages with gzip
+    pub accept_gzip: bool,
+    /// Largest message in bytes that may be sent, or 0 for the default
+    pub max_send_size: i32,
+    /// Largest message in bytes that may be received, or 0 for the default
+    pub max_receive_size: i32,
+    /// Wait for the server to become reachable instead of failing right away
+    pub wait_for_ready: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, TS)]
+#[serde(default, rename_all = "camelCase")]
+pub struct GrpcChannelOptions {
+    /// Milliseconds between HTTP/2 keepalive pings, or 0 to not send them
+    pub keepalive_interval: i32,
+    /// Milliseconds to wait for a ping to be acknowledged, or 0 for the default
+    pub keepalive_timeout: i32,
+    /// Milliseconds an unused connection stays open, or 0 for the default
+    pub idle_timeout: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, TS)]
+#[serde(default, rename_all = "camelCase")]
+pub struct GrpcTlsConfig {
+    /// Accept any server certificate, like for self-signed servers
+    pub skip_verify: bool,
+    /// PEM files of CA certificates to trust, in addition to the system's
+    pub ca_cert_paths: Vec<String>,
+    /// PEM file of the client certificate chain, for mutual TLS
+    pub client_cert_path: Option<String>,
+    /// PEM file of the client certificate's private key
+    pub client_key_path: Option<String>,
+    /// Name to send for SNI and verify the certificate against, instead of the URL's host
+    pub server_name: Option<String>,
+    /// Authority to send with requests, instead of the URL's host and port
+    pub authority: Option<String>,
 }
 
 #[derive(Iden)]
@@ -539,6 +625,10 @@ pub enum GrpcRequestIden {
     AuthenticationType,
     Authentication,
     Metadata,
+    Tls,
+    Protocol,
+    CallOptions,
+    ChannelOptions,
 }
 
 impl<'s> TryFrom<&Row<'s>> for GrpcRequest {
@@ -547,6 +637,9 @@ impl<'s> TryFrom<&Row<'s>> for GrpcRequest {
     fn try_from(r: &Row<'s>) -> Result<Self, Self::Error> {
         let authentication: String = r.get("authentication")?;
         let metadata: String = r.get("metadata")?;
+        let tls: String = r.get("tls")?;
+        let call_options: String = r.get("call_options")?;
+        let channel_options: String = r.get("channel_options")?;
         Ok(GrpcRequest {
             id: r.get("id")?,
             model: r.get("model")?,
@@ -563,6 +656,10 @@ impl<'s> TryFrom<&Row<'s>> for GrpcRequest {
             url: r.get("url")?,
             sort_priority: r.get("sort_priority")?,
             metadata: serde_json::from_str(metadata.as_str()).unwrap_or_default(),
+            tls: serde_json::from_str(tls.as_str()).unwrap_or_default(),
+            protocol: r.get("protocol")?,
+            call_options: serde_json::from_str(call_options.as_str()).unwrap_or_default(),
+            channel_options: serde_json::from_str(channel_options.as_str()).unwrap_or_default(),
         })
     }
 }
@@ -584,6 +681,12 @@ pub struct GrpcConnection {
     pub url: String,
     pub error: Option<String>,
     pub trailers: HashMap<String, String>,
+    /// Initial metadata the server responded with, before any messages
+    pub headers: HashMap<String, String>,
+    /// Encoding the server compressed its messages with, from `grpc-encoding`
+    pub encoding: Option<String>,
+    /// State of the channel after the call, like `ready` when its connection was kept open
+    pub channel_state: Option<String>,
 }
 
 #[derive(Iden)]
@@ -603,6 +706,9 @@ pub enum GrpcConnectionIden {
     Url,
     Error,
     Trailers,
+    Headers,
+    Encoding,
+    ChannelState,
 }
 
 impl<'s> TryFrom<&Row<'s>> for GrpcConnection {
@@ -610,6 +716,7 @@ impl<'s> TryFrom<&Row<'s>> for GrpcConnection {
 
     fn try_from(r: &Row<'s>) -> Result<Self, Self::Error> {
         let trailers: String = r.get("trailers")?;
+        let headers: String = r.get("headers")?;
         Ok(GrpcConnection {
             id: r.get("id")?,
             model: r.get("model")?,
@@ -624,6 +731,9 @@ impl<'s> TryFrom<&Row<'s>> for GrpcConnection {
             url: r.get("url")?,
             error: r.get("error")?,
             trailers: serde_json::from_str(trailers.as_str()).unwrap_or_default(),
+            headers: serde_json::from_str(headers.as_str()).unwrap_or_default(),
+            encoding: r.get("encoding")?,
+            channel_state: r.get("channel_state")?,
         })
     }
 }
@@ -661,6 +771,9 @@ pub struct GrpcEvent {
     pub metadata: HashMap<String, String>,
     pub status: Option<i32>,
     pub error: Option<String>,
+    /// Details of the error's `google.rpc.Status`, decoded like `Any` messages
+    #[ts(type = "Array<Record<string, any>>")]
+    pub error_details: Vec<Value>,
 }
 
 #[derive(Iden)]
@@ -679,6 +792,7 @@ pub enum GrpcEventIden {
     Metadata,
     Status,
     Error,
+    ErrorDetails,
 }
 
 impl<'s> TryFrom<&Row<'s>> for GrpcEvent {
@@ -687,6 +801,7 @@ impl<'s> TryFrom<&Row<'s>> for GrpcEvent {
     fn try_from(r: &Row<'s>) -> Result<Self, Self::Error> {
         let event_type: String = r.get("event_type")?;
         let metadata: String = r.get("metadata")?;
+        let error_details: String = r.get("error_details")?;
         Ok(GrpcEvent {
             id: r.get("id")?,
             model: r.get("model")?,
@@ -700,6 +815,7 @@ impl<'s> TryFrom<&Row<'s>> for GrpcEvent {
             metadata: serde_json::from_str(metadata.as_str()).unwrap_or_default(),
             status: r.get("status")?,
             error: r.get("error")?,
+            error_details: serde_json::from_str(error_details.as_str()).unwrap_or_default(),
         })
     }
 }
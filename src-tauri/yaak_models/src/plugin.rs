@@ -1,4 +1,5 @@
-use log::info;
+use crate::secrets::init_secrets_key;
+use log::{info, warn};
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
 use serde::Deserialize;
@@ -38,6 +39,10 @@ impl Builder {
                 let app_path = app.path().app_data_dir().unwrap();
                 create_dir_all(app_path.clone()).expect("Problem creating App directory!");
 
+                if let Err(e) = init_secrets_key(&app_path) {
+                    warn!("Failed to initialize secrets key {}", e);
+                }
+
                 let db_file_path = app_path.join("db.sqlite");
                 info!("Opening SQLite DB at {db_file_path:?}");
 
@@ -1,8 +1,10 @@
 use std::fs;
 
+use crate::error::Error::ValidationError;
 use crate::error::Result;
-use crate::models::{CookieJar, CookieJarIden, Environment, EnvironmentIden, Folder, FolderIden, GrpcConnection, GrpcConnectionIden, GrpcEvent, GrpcEventIden, GrpcRequest, GrpcRequestIden, HttpRequest, HttpRequestIden, HttpResponse, HttpResponseHeader, HttpResponseIden, KeyValue, KeyValueIden, ModelType, Plugin, PluginIden, Settings, SettingsIden, Workspace, WorkspaceIden};
+use crate::models::{CookieJar, CookieJarIden, Environment, EnvironmentIden, EnvironmentVariable, EnvironmentVariableType, Folder, FolderIden, GrpcConnection, GrpcConnectionIden, GrpcEvent, GrpcEventIden, GrpcRequest, GrpcRequestIden, HttpRequest, HttpRequestIden, HttpResponse, HttpResponseHeader, HttpResponseIden, KeyValue, KeyValueIden, ModelType, Plugin, PluginIden, Settings, SettingsIden, Workspace, WorkspaceIden};
 use crate::plugin::SqliteConnection;
+use crate::secrets::{encrypt_variables, is_encrypted};
 use log::{debug, error};
 use rand::distributions::{Alphanumeric, DistString};
 use sea_query::ColumnRef::Asterisk;
@@ -171,6 +173,7 @@ pub async fn upsert_workspace<R: Runtime>(
         _ => workspace.id.to_string(),
     };
     let trimmed_name = workspace.name.trim();
+    validate_variable_types(&workspace.variables)?;
 
     let dbm = &*window.app_handle().state::<SqliteConnection>();
     let db = dbm.0.lock().await.get().unwrap();
@@ -194,7 +197,7 @@ pub async fn upsert_workspace<R: Runtime>(
             CurrentTimestamp.into(),
             trimmed_name.into(),
             workspace.description.into(),
-            serde_json::to_string(&workspace.variables)?.into(),
+            serde_json::to_string(&encrypt_variables(&workspace.variables)?)?.into(),
             workspace.setting_request_timeout.into(),
             workspace.setting_follow_redirects.into(),
             workspace.setting_validate_certificates.into(),
@@ -343,6 +346,10 @@ pub async fn upsert_grpc_request<R: Runtime>(
             GrpcRequestIden::AuthenticationType,
             GrpcRequestIden::Authentication,
             GrpcRequestIden::Metadata,
+            GrpcRequestIden::Tls,
+            GrpcRequestIden::Protocol,
+            GrpcRequestIden::CallOptions,
+            GrpcRequestIden::ChannelOptions,
         ])
         .values_panic([
             id.as_str().into(),
@@ -363,6 +370,10 @@ pub async fn upsert_grpc_request<R: Runtime>(
                 .into(),
             serde_json::to_string(&request.authentication)?.into(),
             serde_json::to_string(&request.metadata)?.into(),
+            serde_json::to_string(&request.tls)?.into(),
+            request.protocol.as_str().into(),
+            serde_json::to_string(&request.call_options)?.into(),
+            serde_json::to_string(&request.channel_options)?.into(),
         ])
         .on_conflict(
             OnConflict::column(GrpcRequestIden::Id)
@@ -379,6 +390,10 @@ pub async fn upsert_grpc_request<R: Runtime>(
                     GrpcRequestIden::AuthenticationType,
                     GrpcRequestIden::Authentication,
                     GrpcRequestIden::Metadata,
+                    GrpcRequestIden::Tls,
+                    GrpcRequestIden::Protocol,
+                    GrpcRequestIden::CallOptions,
+                    GrpcRequestIden::ChannelOptions,
                 ])
                 .to_owned(),
         )
@@ -443,7 +458,10 @@ pub async fn upsert_grpc_connection<R: Runtime>(
             GrpcConnectionIden::Status,
             GrpcConnectionIden::Error,
             GrpcConnectionIden::Trailers,
+            GrpcConnectionIden::Headers,
             GrpcConnectionIden::Url,
+            GrpcConnectionIden::Encoding,
+            GrpcConnectionIden::ChannelState,
         ])
         .values_panic([
             id.as_str().into(),
@@ -457,7 +475,10 @@ pub async fn upsert_grpc_connection<R: Runtime>(
             connection.status.into(),
             connection.error.as_ref().map(|s| s.as_str()).into(),
             serde_json::to_string(&connection.trailers)?.into(),
+            serde_json::to_string(&connection.headers)?.into(),
             connection.url.as_str().into(),
+            connection.encoding.as_ref().map(|s| s.as_str()).into(),
+            connection.channel_state.as_ref().map(|s| s.as_str()).into(),
         ])
         .on_conflict(
             OnConflict::column(GrpcConnectionIden::Id)
@@ -469,7 +490,10 @@ pub async fn upsert_grpc_connection<R: Runtime>(
                     GrpcConnectionIden::Status,
                     GrpcConnectionIden::Error,
                     GrpcConnectionIden::Trailers,
+                    GrpcConnectionIden::Headers,
                     GrpcConnectionIden::Url,
+                    GrpcConnectionIden::Encoding,
+                    GrpcConnectionIden::ChannelState,
                 ])
                 .to_owned(),
         )
@@ -567,6 +591,7 @@ pub async fn upsert_grpc_event<R: Runtime>(
             GrpcEventIden::Metadata,
             GrpcEventIden::Status,
             GrpcEventIden::Error,
+            GrpcEventIden::ErrorDetails,
         ])
         .values_panic([
             id.as_str().into(),
@@ -580,6 +605,7 @@ pub async fn upsert_grpc_event<R: Runtime>(
             serde_json::to_string(&event.metadata)?.into(),
             event.status.into(),
             event.error.as_ref().map(|s| s.as_str()).into(),
+            serde_json::to_string(&event.error_details)?.into(),
         ])
         .on_conflict(
             OnConflict::column(GrpcEventIden::Id)
@@ -590,6 +616,7 @@ pub async fn upsert_grpc_event<R: Runtime>(
                     GrpcEventIden::Metadata,
                     GrpcEventIden::Status,
                     GrpcEventIden::Error,
+                    GrpcEventIden::ErrorDetails,
                 ])
                 .to_owned(),
         )
@@ -811,6 +838,44 @@ pub async fn update_settings<R: Runtime>(
     Ok(emit_upserted_model(window, m))
 }
 
+/// Make sure typed variables hold what their type says. Values containing template tags are
+/// only known once rendered, so they're skipped.
+fn validate_variable_types(variables: &[EnvironmentVariable]) -> Result<()> {
+    for v in variables {
+        let value = v.value.as_str();
+        if value.is_empty() || value.contains("${[") || is_encrypted(value) {
+            continue;
+        }
+
+        let value_type = match v.value_type {
+            Some(t) => t,
+            None => continue,
+        };
+
+        let valid = match value_type {
+            EnvironmentVariableType::String => !value.contains('\n'),
+            EnvironmentVariableType::Multiline => true,
+            EnvironmentVariableType::Number => {
+                serde_json::from_str::<serde_json::Number>(value.trim()).is_ok()
+            }
+            EnvironmentVariableType::Boolean => value.trim().parse::<bool>().is_ok(),
+            EnvironmentVariableType::Json => {
+                serde_json::from_str::<serde_json::Value>(value).is_ok()
+            }
+        };
+
+        if !valid {
+            let type_name = format!("{value_type:?}").to_lowercase();
+            return Err(ValidationError(format!(
+                "Variable {} is not a valid {}",
+                v.name, type_name
+            )));
+        }
+    }
+
+    Ok(())
+}
+
 pub async fn upsert_environment<R: Runtime>(
     window: &WebviewWindow<R>,
     environment: Environment,
@@ -821,6 +886,27 @@ pub async fn upsert_environment<R: Runtime>(
     };
     let trimmed_name = environment.name.trim();
 
+    // Make sure the parent exists and doesn't eventually point back to this environment
+    let mut parent_id = environment.environment_id.clone();
+    while let Some(pid) = parent_id {
+        if pid == id {
+            return Err(ValidationError(format!(
+                "Environment {} can't extend itself",
+                trimmed_name
+            )));
+        }
+        let parent = get_environment(window, pid.as_str()).await?;
+        if parent.workspace_id != environment.workspace_id {
+            return Err(ValidationError(format!(
+                "Environment {} can't extend an environment from another workspace",
+                trimmed_name
+            )));
+        }
+        parent_id = parent.environment_id;
+    }
+
+    validate_variable_types(&environment.variables)?;
+
     let dbm = &*window.app_handle().state::<SqliteConnection>();
     let db = dbm.0.lock().await.get().unwrap();
 
@@ -831,23 +917,29 @@ pub async fn upsert_environment<R: Runtime>(
             EnvironmentIden::CreatedAt,
             EnvironmentIden::UpdatedAt,
             EnvironmentIden::WorkspaceId,
+            EnvironmentIden::EnvironmentId,
             EnvironmentIden::Name,
             EnvironmentIden::Variables,
+            EnvironmentIden::EnvFiles,
         ])
         .values_panic([
             id.as_str().into(),
             CurrentTimestamp.into(),
             CurrentTimestamp.into(),
             environment.workspace_id.as_str().into(),
+            environment.environment_id.as_ref().map(|s| s.as_str()).into(),
             trimmed_name.into(),
-            serde_json::to_string(&environment.variables)?.into(),
+            serde_json::to_string(&encrypt_variables(&environment.variables)?)?.into(),
+            serde_json::to_string(&environment.env_files)?.into(),
         ])
         .on_conflict(
             OnConflict::column(EnvironmentIden::Id)
                 .update_columns([
                     EnvironmentIden::UpdatedAt,
+                    EnvironmentIden::EnvironmentId,
                     EnvironmentIden::Name,
                     EnvironmentIden::Variables,
+                    EnvironmentIden::EnvFiles,
                 ])
                 .to_owned(),
         )
@@ -1006,6 +1098,7 @@ pub async fn upsert_folder<R: Runtime>(window: &WebviewWindow<R>, r: Folder) ->
         _ => r.id.to_string(),
     };
     let trimmed_name = r.name.trim();
+    validate_variable_types(&r.variables)?;
 
     let dbm = &*window.app_handle().state::<SqliteConnection>();
     let db = dbm.0.lock().await.get().unwrap();
@@ -1020,6 +1113,7 @@ pub async fn upsert_folder<R: Runtime>(window: &WebviewWindow<R>, r: Folder) ->
             FolderIden::FolderId,
             FolderIden::Name,
             FolderIden::SortPriority,
+            FolderIden::Variables,
         ])
         .values_panic([
             id.as_str().into(),
@@ -1029,6 +1123,7 @@ pub async fn upsert_folder<R: Runtime>(window: &WebviewWindow<R>, r: Folder) ->
             r.folder_id.as_ref().map(|s| s.as_str()).into(),
             trimmed_name.into(),
             r.sort_priority.into(),
+            serde_json::to_string(&encrypt_variables(&r.variables)?)?.into(),
         ])
         .on_conflict(
             OnConflict::column(GrpcEventIden::Id)
@@ -1037,6 +1132,7 @@ pub async fn upsert_folder<R: Runtime>(window: &WebviewWindow<R>, r: Folder) ->
                     FolderIden::Name,
                     FolderIden::FolderId,
                     FolderIden::SortPriority,
+                    FolderIden::Variables,
                 ])
                 .to_owned(),
         )
@@ -1066,6 +1162,7 @@ pub async fn upsert_http_request<R: Runtime>(
         _ => r.id.to_string(),
     };
     let trimmed_name = r.name.trim();
+    validate_variable_types(&r.variables)?;
 
     let dbm = &*window.app_handle().state::<SqliteConnection>();
     let db = dbm.0.lock().await.get().unwrap();
@@ -1088,6 +1185,7 @@ pub async fn upsert_http_request<R: Runtime>(
             HttpRequestIden::AuthenticationType,
             HttpRequestIden::Headers,
             HttpRequestIden::SortPriority,
+            HttpRequestIden::Variables,
         ])
         .values_panic([
             id.as_str().into(),
@@ -1105,6 +1203,7 @@ pub async fn upsert_http_request<R: Runtime>(
             r.authentication_type.as_ref().map(|s| s.as_str()).into(),
             serde_json::to_string(&r.headers)?.into(),
             r.sort_priority.into(),
+            serde_json::to_string(&encrypt_variables(&r.variables)?)?.into(),
         ])
         .on_conflict(
             OnConflict::column(GrpcEventIden::Id)
@@ -1122,6 +1221,7 @@ pub async fn upsert_http_request<R: Runtime>(
                     HttpRequestIden::Url,
                     HttpRequestIden::UrlParameters,
                     HttpRequestIden::SortPriority,
+                    HttpRequestIden::Variables,
                 ])
                 .to_owned(),
         )
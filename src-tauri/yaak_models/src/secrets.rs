@@ -0,0 +1,159 @@
+use crate::error::Error::SecretsError;
+use crate::error::Result;
+use crate::models::EnvironmentVariable;
+use base64::Engine;
+use log::info;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN};
+use ring::pbkdf2;
+use ring::rand::{SecureRandom, SystemRandom};
+use std::fs;
+use std::num::NonZeroU32;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Shown in place of secret values anywhere they shouldn't be visible
+pub const SECRET_MASK: &str = "••••••••";
+
+/// If set, the secrets key is derived from this instead of read from the keyfile
+const PASSPHRASE_ENV_VAR: &str = "YAAK_SECRETS_PASSPHRASE";
+const KEY_FILE: &str = "secrets.key";
+const SALT_FILE: &str = "secrets.salt";
+const PBKDF2_ITERATIONS: u32 = 600_000;
+const ENCRYPTED_PREFIX: &str = "yenc_v1:";
+
+static KEY: OnceLock<LessSafeKey> = OnceLock::new();
+
+pub fn init_secrets_key(app_data_dir: &Path) -> Result<()> {
+    let key_bytes = match std::env::var(PASSPHRASE_ENV_VAR) {
+        Ok(passphrase) if !passphrase.is_empty() => {
+            info!("Using secrets key derived from passphrase");
+            let salt = read_or_create_random(&app_data_dir.join(SALT_FILE), 16)?;
+            let mut key = vec![0u8; 32];
+            pbkdf2::derive(
+                pbkdf2::PBKDF2_HMAC_SHA256,
+                NonZeroU32::new(PBKDF2_ITERATIONS).unwrap(),
+                &salt,
+                passphrase.as_bytes(),
+                &mut key,
+            );
+            key
+        }
+        _ => read_or_create_random(&app_data_dir.join(KEY_FILE), 32)?,
+    };
+
+    let key = UnboundKey::new(&CHACHA20_POLY1305, &key_bytes)
+        .map_err(|_| SecretsError("Invalid secrets key".to_string()))?;
+    let _ = KEY.set(LessSafeKey::new(key));
+    Ok(())
+}
+
+fn read_or_create_random(path: &Path, len: usize) -> Result<Vec<u8>> {
+    if let Ok(bytes) = fs::read(path) {
+        if bytes.len() == len {
+            return Ok(bytes);
+        }
+        return Err(SecretsError(format!("Invalid secrets file {path:?}")));
+    }
+
+    let mut bytes = vec![0u8; len];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| SecretsError("Failed to generate secrets key".to_string()))?;
+    fs::write(path, &bytes).map_err(|e| SecretsError(e.to_string()))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let permissions = fs::Permissions::from_mode(0o600);
+        fs::set_permissions(path, permissions).map_err(|e| SecretsError(e.to_string()))?;
+    }
+
+    info!("Created secrets file at {path:?}");
+    Ok(bytes)
+}
+
+fn encrypt_value(value: &str) -> Result<String> {
+    // Models keep secrets encrypted, so saving them again leaves the value as-is
+    if value.is_empty() || is_encrypted(value) {
+        return Ok(value.to_string());
+    }
+
+    let key = KEY
+        .get()
+        .ok_or(SecretsError("Secrets key not initialized".to_string()))?;
+
+    let mut nonce_bytes = [0u8; NONCE_LEN];
+    SystemRandom::new()
+        .fill(&mut nonce_bytes)
+        .map_err(|_| SecretsError("Failed to generate nonce".to_string()))?;
+
+    let mut data = value.as_bytes().to_vec();
+    key.seal_in_place_append_tag(
+        Nonce::assume_unique_for_key(nonce_bytes),
+        Aad::empty(),
+        &mut data,
+    )
+    .map_err(|_| SecretsError("Failed to encrypt value".to_string()))?;
+
+    let mut encrypted = nonce_bytes.to_vec();
+    encrypted.append(&mut data);
+    let encoded = base64::engine::general_purpose::STANDARD.encode(encrypted);
+    Ok(format!("{ENCRYPTED_PREFIX}{encoded}"))
+}
+
+/// Whether the value is an encrypted secret, as secret values are everywhere but when sending
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(ENCRYPTED_PREFIX)
+}
+
+fn decrypt_value(value: &str) -> Option<String> {
+    let encoded = value.strip_prefix(ENCRYPTED_PREFIX)?;
+    let key = KEY.get()?;
+    let mut data = base64::engine::general_purpose::STANDARD
+        .decode(encoded)
+        .ok()?;
+    if data.len() < NONCE_LEN {
+        return None;
+    }
+
+    let mut ciphertext = data.split_off(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(&data).ok()?;
+    let plaintext = key
+        .open_in_place(nonce, Aad::empty(), &mut ciphertext)
+        .ok()?;
+    String::from_utf8(plaintext.to_vec()).ok()
+}
+
+/// Encrypt the values of secret variables, for storing in the database. Secret values are
+/// only ever decrypted to be sent, so variables that are no longer secret are decrypted here.
+pub fn encrypt_variables(variables: &[EnvironmentVariable]) -> Result<Vec<EnvironmentVariable>> {
+    let mut encrypted = Vec::new();
+    for v in variables {
+        let value = match v.secret {
+            true => encrypt_value(v.value.as_str())?,
+            false => decrypt_value(v.value.as_str()).unwrap_or(v.value.to_string()),
+        };
+        encrypted.push(EnvironmentVariable {
+            value,
+            ..v.to_owned()
+        });
+    }
+    Ok(encrypted)
+}
+
+/// The plaintext of a secret variable's value, for rendering it to send. Values saved before
+/// they were secret aren't encrypted yet, so they're returned as-is. Returns `None` when the
+/// value can't be decrypted, like when the key has changed.
+pub fn decrypt_secret(value: &str) -> Option<String> {
+    match is_encrypted(value) {
+        true => decrypt_value(value),
+        false => Some(value.to_string()),
+    }
+}
+
+/// Clear the values of secret variables, for places they shouldn't leave the app
+pub fn redact_variables(variables: &mut [EnvironmentVariable]) {
+    for v in variables.iter_mut().filter(|v| v.secret) {
+        v.value = "".to_string();
+    }
+}
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::collections::HashMap;
 use ts_rs::TS;
 
@@ -164,6 +165,8 @@ pub struct RenderHttpRequestRequest {
 #[ts(export)]
 pub struct RenderHttpRequestResponse {
     pub http_request: HttpRequest,
+    /// Why the request couldn't be rendered, in which case it's returned unrendered
+    pub error: Option<String>,
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize, TS)]
@@ -206,6 +209,11 @@ pub struct GetTemplateFunctionsResponse {
 pub struct TemplateFunction {
     pub name: String,
     pub args: Vec<TemplateFunctionArg>,
+    /// Whether calls with the same args return the same value, so they only need to run
+    /// once per send. Functions that don't say are treated as deterministic, so ones like
+    /// random values need to set this to false to run every time.
+    #[ts(optional = nullable)]
+    pub deterministic: Option<bool>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
@@ -294,7 +302,11 @@ pub struct CallTemplateFunctionResponse {
 #[ts(export)]
 pub struct CallTemplateFunctionArgs {
     pub purpose: RenderPurpose,
+    /// Arg values as strings, with numbers, lists, and objects encoded as JSON
     pub values: HashMap<String, String>,
+    /// The same arg values, but keeping their types
+    #[ts(type = "Record<string, any>")]
+    pub typed_values: HashMap<String, Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
@@ -5,6 +5,7 @@ use crate::events::{
     GetHttpRequestActionsRequest, GetHttpRequestActionsResponse, GetTemplateFunctionsResponse,
     ImportRequest, ImportResponse, InternalEvent, InternalEventPayload, RenderPurpose,
 };
+use serde_json::Value;
 use std::collections::HashMap;
 
 use crate::error::Error::PluginErr;
@@ -13,13 +14,15 @@ use crate::plugin::start_server;
 use crate::server::PluginRuntimeGrpcServer;
 use std::time::Duration;
 use tauri::{AppHandle, Runtime};
-use tokio::sync::mpsc;
 use tokio::sync::watch::Sender;
+use tokio::sync::{mpsc, Mutex};
 use crate::handle::PluginHandle;
 
 pub struct PluginManager {
     kill_tx: Sender<bool>,
     server: PluginRuntimeGrpcServer,
+    // Template function definitions, along with the plugins version they were fetched at
+    template_functions: Mutex<Option<(usize, Vec<GetTemplateFunctionsResponse>)>>,
 }
 
 impl PluginManager {
@@ -36,7 +39,11 @@ impl PluginManager {
             .await
             .expect("Failed to start plugin runtime");
 
-        PluginManager { kill_tx, server }
+        PluginManager {
+            kill_tx,
+            server,
+            template_functions: Mutex::new(None),
+        }
     }
 
     pub async fn reload_all(&self) {
@@ -96,6 +103,21 @@ impl PluginManager {
     }
 
     pub async fn get_template_functions(&self) -> Result<Vec<GetTemplateFunctionsResponse>> {
+        // Hold the lock while fetching so concurrent callers wait for a single round trip
+        let mut cached = self.template_functions.lock().await;
+        let version = self.server.plugins_version();
+        if let Some((v, functions)) = cached.as_ref() {
+            if *v == version {
+                return Ok(functions.clone());
+            }
+        }
+
+        let functions = self.fetch_template_functions().await?;
+        *cached = Some((version, functions.clone()));
+        Ok(functions)
+    }
+
+    async fn fetch_template_functions(&self) -> Result<Vec<GetTemplateFunctionsResponse>> {
         let reply_events = self
             .server
             .send_and_wait(&InternalEventPayload::GetTemplateFunctionsRequest)
@@ -127,14 +149,18 @@ impl PluginManager {
     pub async fn call_template_function(
         &self,
         fn_name: &str,
-        args: HashMap<String, String>,
+        args: HashMap<String, Value>,
         purpose: RenderPurpose,
     ) -> Result<Option<String>> {
         let req = CallTemplateFunctionRequest {
             name: fn_name.to_string(),
             args: CallTemplateFunctionArgs {
                 purpose,
-                values: args,
+                values: args
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), value_to_arg_string(v)))
+                    .collect(),
+                typed_values: args,
             },
         };
 
@@ -211,3 +237,12 @@ impl PluginManager {
         }
     }
 }
+
+/// Plugins written before args had types expect every value to be a string
+fn value_to_arg_string(v: &Value) -> String {
+    match v {
+        Value::String(s) => s.to_string(),
+        Value::Null => "".to_string(),
+        v => v.to_string(),
+    }
+}
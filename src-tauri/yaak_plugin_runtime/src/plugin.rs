@@ -83,6 +83,12 @@ pub async fn start_server(
                     } => {
                         server.boot_plugin(plugin_ref_id.as_str(), &resp).await;
                     }
+                    InternalEvent {
+                        payload: InternalEventPayload::ReloadResponse(_),
+                        ..
+                    } => {
+                        server.plugins_changed();
+                    }
                     _ => {}
                 };
             }
@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use log::warn;
 use tokio::sync::mpsc::Receiver;
@@ -29,6 +30,7 @@ pub struct PluginRuntimeGrpcServer {
     plugin_ref_to_plugin: Arc<Mutex<HashMap<String, PluginHandle>>>,
     callback_to_plugin_ref: Arc<Mutex<HashMap<String, String>>>,
     subscribers: Arc<Mutex<HashMap<String, mpsc::Sender<InternalEvent>>>>,
+    plugins_version: Arc<AtomicUsize>,
     plugin_dirs: Vec<String>,
 }
 
@@ -38,6 +40,7 @@ impl PluginRuntimeGrpcServer {
             plugin_ref_to_plugin: Arc::new(Mutex::new(HashMap::new())),
             callback_to_plugin_ref: Arc::new(Mutex::new(HashMap::new())),
             subscribers: Arc::new(Mutex::new(HashMap::new())),
+            plugins_version: Arc::new(AtomicUsize::new(0)),
             plugin_dirs,
         }
     }
@@ -51,6 +54,16 @@ impl PluginRuntimeGrpcServer {
             .collect::<Vec<PluginHandle>>()
     }
 
+    /// Incremented whenever a plugin is added, booted, reloaded, or removed, so
+    /// anything derived from the set of plugins knows when to refresh
+    pub fn plugins_version(&self) -> usize {
+        self.plugins_version.load(Ordering::SeqCst)
+    }
+
+    pub fn plugins_changed(&self) {
+        self.plugins_version.fetch_add(1, Ordering::SeqCst);
+    }
+
     pub async fn subscribe(&self) -> (String, Receiver<InternalEvent>) {
         let (tx, rx) = mpsc::channel(128);
         let rx_id = generate_id();
@@ -69,17 +82,23 @@ impl PluginRuntimeGrpcServer {
     }
 
     pub async fn remove_plugin(&self, id: &str) {
-        match self.plugin_ref_to_plugin.lock().await.remove(id) {
+        // Only bump the version once the plugin is gone, so functions fetched in the meantime
+        // aren't cached under the new version
+        let removed = self.plugin_ref_to_plugin.lock().await.remove(id);
+        self.plugins_changed();
+        match removed {
             None => println!("Tried to remove non-existing plugin {}", id),
             Some(plugin) => println!("Removed plugin {} {}", id, plugin.name().await),
         };
     }
 
     pub async fn boot_plugin(&self, id: &str, resp: &PluginBootResponse) {
-        match self.plugin_ref_to_plugin.lock().await.get(id) {
+        let plugin = self.plugin_ref_to_plugin.lock().await.get(id).cloned();
+        match plugin {
             None => println!("Tried booting non-existing plugin {}", id),
-            Some(plugin) => plugin.clone().boot(resp).await,
+            Some(plugin) => plugin.boot(resp).await,
         }
+        self.plugins_changed();
     }
 
     pub async fn add_plugin(
@@ -99,6 +118,7 @@ impl PluginRuntimeGrpcServer {
             .lock()
             .await
             .insert(ref_id, plugin_handle.clone());
+        self.plugins_changed();
         plugin_handle
     }
 
@@ -247,6 +267,7 @@ impl PluginRuntimeGrpcServer {
     }
 
     pub async fn reload_plugins(&self) {
+        self.plugins_changed();
         for (_, plugin) in self.plugin_ref_to_plugin.lock().await.clone() {
             if let Err(e) = plugin.reload().await {
                 warn!("Failed to reload plugin {} {}", plugin.dir, e)
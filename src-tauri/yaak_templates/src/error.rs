@@ -0,0 +1,30 @@
+use crate::ParseError;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum Error {
+    #[error("Failed to parse template: {0}")]
+    ParseErr(ParseError),
+    #[error("Variable \"{name}\" is not defined{}", at_position(.position))]
+    VariableNotDefinedErr {
+        name: String,
+        position: Option<usize>,
+    },
+    #[error("Failed to run template function {name}(){}: {message}", at_position(.position))]
+    FunctionErr {
+        name: String,
+        message: String,
+        position: Option<usize>,
+    },
+    #[error("Variable cycle detected: {}", .cycle.join(" -> "))]
+    VariableCycleErr { cycle: Vec<String> },
+}
+
+fn at_position(position: &Option<usize>) -> String {
+    match position {
+        Some(p) => format!(" at position {p}"),
+        None => "".to_string(),
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
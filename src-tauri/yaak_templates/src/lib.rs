@@ -1,5 +1,11 @@
+pub mod error;
+pub mod native;
 pub mod parser;
+pub mod references;
 pub mod renderer;
+pub mod variables;
 
 pub use parser::*;
-pub use renderer::*;
\ No newline at end of file
+pub use references::*;
+pub use renderer::*;
+pub use variables::*;
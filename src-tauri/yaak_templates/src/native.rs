@@ -0,0 +1,448 @@
+use base64::Engine;
+use chrono::{Duration, SecondsFormat, Utc};
+use hmac::{Hmac, Mac};
+use md5::Md5;
+use rand::distributions::Alphanumeric;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde_json::Value;
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+type Args = HashMap<String, Value>;
+
+/// A template function implemented in Rust, so it doesn't need the plugin runtime
+pub struct NativeFunction {
+    pub name: &'static str,
+    pub args: &'static [&'static str],
+    /// Whether calls with the same args always return the same value
+    pub deterministic: bool,
+    run: fn(&Args) -> Result<String, String>,
+}
+
+impl NativeFunction {
+    pub fn call(&self, args: &Args) -> Result<String, String> {
+        (self.run)(args)
+    }
+}
+
+pub const NATIVE_FUNCTIONS: &[NativeFunction] = &[
+    NativeFunction {
+        name: "uuid_v4",
+        args: &[],
+        deterministic: false,
+        run: uuid_v4,
+    },
+    NativeFunction {
+        name: "uuid_v7",
+        args: &[],
+        deterministic: false,
+        run: uuid_v7,
+    },
+    NativeFunction {
+        name: "timestamp",
+        args: &["format", "offset"],
+        deterministic: false,
+        run: timestamp,
+    },
+    NativeFunction {
+        name: "random_int",
+        args: &["min", "max"],
+        deterministic: false,
+        run: random_int,
+    },
+    NativeFunction {
+        name: "random_string",
+        args: &["length", "charset"],
+        deterministic: false,
+        run: random_string,
+    },
+    NativeFunction {
+        name: "base64_encode",
+        args: &["value"],
+        deterministic: true,
+        run: base64_encode,
+    },
+    NativeFunction {
+        name: "base64_decode",
+        args: &["value"],
+        deterministic: true,
+        run: base64_decode,
+    },
+    NativeFunction {
+        name: "url_encode",
+        args: &["value"],
+        deterministic: true,
+        run: url_encode,
+    },
+    NativeFunction {
+        name: "url_decode",
+        args: &["value"],
+        deterministic: true,
+        run: url_decode,
+    },
+    NativeFunction {
+        name: "hash",
+        args: &["value", "algorithm", "encoding"],
+        deterministic: true,
+        run: hash,
+    },
+    NativeFunction {
+        name: "hmac",
+        args: &["value", "key", "algorithm", "encoding"],
+        deterministic: true,
+        run: hmac,
+    },
+    NativeFunction {
+        name: "json_escape",
+        args: &["value"],
+        deterministic: true,
+        run: json_escape,
+    },
+];
+
+pub fn find_native_function(name: &str) -> Option<&'static NativeFunction> {
+    NATIVE_FUNCTIONS.iter().find(|f| f.name == name)
+}
+
+pub fn arg_str(args: &Args, name: &str) -> Option<String> {
+    match args.get(name) {
+        None | Some(Value::Null) => None,
+        Some(Value::String(s)) => Some(s.to_string()),
+        Some(v) => Some(v.to_string()),
+    }
+}
+
+pub fn arg_i64(args: &Args, name: &str, default: i64) -> Result<i64, String> {
+    match args.get(name) {
+        None | Some(Value::Null) => Ok(default),
+        Some(Value::Number(n)) => n
+            .as_i64()
+            .ok_or(format!("Argument {name} must be an integer")),
+        Some(Value::String(s)) if s.is_empty() => Ok(default),
+        Some(Value::String(s)) => s
+            .trim()
+            .parse()
+            .map_err(|_| format!("Argument {name} must be an integer")),
+        Some(_) => Err(format!("Argument {name} must be an integer")),
+    }
+}
+
+pub fn required_str(args: &Args, name: &str) -> Result<String, String> {
+    arg_str(args, name).ok_or(format!("Argument {name} is required"))
+}
+
+fn uuid_v4(_args: &Args) -> Result<String, String> {
+    Ok(uuid::Uuid::new_v4().to_string())
+}
+
+fn uuid_v7(_args: &Args) -> Result<String, String> {
+    let millis = Utc::now().timestamp_millis() as u64;
+    let random_bytes = rand::random::<[u8; 10]>();
+    let id = uuid::Builder::from_unix_timestamp_millis(millis, &random_bytes).into_uuid();
+    Ok(id.to_string())
+}
+
+fn timestamp(args: &Args) -> Result<String, String> {
+    let offset = match arg_str(args, "offset") {
+        Some(o) => parse_offset(o.as_str())?,
+        None => Duration::zero(),
+    };
+    let now = Utc::now() + offset;
+
+    let format = arg_str(args, "format").unwrap_or_default();
+    match format.as_str() {
+        "" | "iso8601" => Ok(now.to_rfc3339_opts(SecondsFormat::Millis, true)),
+        "unix" => Ok(now.timestamp().to_string()),
+        "unix_millis" => Ok(now.timestamp_millis().to_string()),
+        f => {
+            // Invalid format strings make Display fail, so don't use to_string()
+            let mut s = String::new();
+            write!(s, "{}", now.format(f)).map_err(|_| format!("Invalid timestamp format {f}"))?;
+            Ok(s)
+        }
+    }
+}
+
+/// Parse offsets like `-1d`, `+2h30m`, or `90s`
+fn parse_offset(offset: &str) -> Result<Duration, String> {
+    let offset = offset.trim();
+    let (negative, units) = match offset.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, offset.strip_prefix('+').unwrap_or(offset)),
+    };
+
+    let mut total = Duration::zero();
+    let mut chars = units.chars().peekable();
+    while chars.peek().is_some() {
+        let mut amount = String::new();
+        while let Some(ch) = chars.next_if(|c| c.is_ascii_digit()) {
+            amount.push(ch);
+        }
+        let mut unit = String::new();
+        while let Some(ch) = chars.next_if(|c| c.is_ascii_alphabetic()) {
+            unit.push(ch);
+        }
+
+        let amount: i64 = amount
+            .parse()
+            .map_err(|_| format!("Invalid timestamp offset {offset}"))?;
+        total += match unit.as_str() {
+            "ms" => Duration::milliseconds(amount),
+            "s" => Duration::seconds(amount),
+            "m" => Duration::minutes(amount),
+            "h" => Duration::hours(amount),
+            "d" => Duration::days(amount),
+            "w" => Duration::weeks(amount),
+            _ => return Err(format!("Invalid timestamp offset {offset}")),
+        };
+    }
+
+    Ok(if negative { -total } else { total })
+}
+
+fn random_int(args: &Args) -> Result<String, String> {
+    let min = arg_i64(args, "min", 0)?;
+    let max = arg_i64(args, "max", 100)?;
+    if min > max {
+        return Err(format!("min ({min}) must not be greater than max ({max})"));
+    }
+    Ok(rand::thread_rng().gen_range(min..=max).to_string())
+}
+
+fn random_string(args: &Args) -> Result<String, String> {
+    let length = arg_i64(args, "length", 16)?;
+    let length = usize::try_from(length).map_err(|_| "length must not be negative")?;
+    let mut rng = rand::thread_rng();
+
+    let s = match arg_str(args, "charset") {
+        None => (0..length)
+            .map(|_| rng.sample(Alphanumeric) as char)
+            .collect(),
+        Some(charset) => {
+            let charset = charset.chars().collect::<Vec<char>>();
+            if charset.is_empty() {
+                return Err("charset must not be empty".to_string());
+            }
+            (0..length)
+                .map(|_| *charset.choose(&mut rng).unwrap())
+                .collect()
+        }
+    };
+
+    Ok(s)
+}
+
+fn base64_encode(args: &Args) -> Result<String, String> {
+    let value = required_str(args, "value")?;
+    Ok(base64::engine::general_purpose::STANDARD.encode(value))
+}
+
+fn base64_decode(args: &Args) -> Result<String, String> {
+    let value = required_str(args, "value")?;
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(value.trim())
+        .map_err(|e| e.to_string())?;
+    String::from_utf8(bytes).map_err(|e| e.to_string())
+}
+
+fn url_encode(args: &Args) -> Result<String, String> {
+    let value = required_str(args, "value")?;
+    Ok(urlencoding::encode(value.as_str()).to_string())
+}
+
+fn url_decode(args: &Args) -> Result<String, String> {
+    let value = required_str(args, "value")?;
+    urlencoding::decode(value.as_str())
+        .map(|v| v.to_string())
+        .map_err(|e| e.to_string())
+}
+
+fn hash(args: &Args) -> Result<String, String> {
+    let value = required_str(args, "value")?;
+    let algorithm = arg_str(args, "algorithm").unwrap_or("sha256".to_string());
+    let bytes = match algorithm.as_str() {
+        "md5" => Md5::digest(value).to_vec(),
+        "sha1" => Sha1::digest(value).to_vec(),
+        "sha256" => Sha256::digest(value).to_vec(),
+        "sha512" => Sha512::digest(value).to_vec(),
+        a => return Err(format!("Unsupported hash algorithm {a}")),
+    };
+    encode_bytes(bytes, arg_str(args, "encoding"))
+}
+
+fn hmac(args: &Args) -> Result<String, String> {
+    let value = required_str(args, "value")?;
+    let key = required_str(args, "key")?;
+    let algorithm = arg_str(args, "algorithm").unwrap_or("sha256".to_string());
+    let bytes = match algorithm.as_str() {
+        "md5" => hmac_bytes::<Hmac<Md5>>(&key, &value),
+        "sha1" => hmac_bytes::<Hmac<Sha1>>(&key, &value),
+        "sha256" => hmac_bytes::<Hmac<Sha256>>(&key, &value),
+        "sha512" => hmac_bytes::<Hmac<Sha512>>(&key, &value),
+        a => return Err(format!("Unsupported HMAC algorithm {a}")),
+    }?;
+    encode_bytes(bytes, arg_str(args, "encoding"))
+}
+
+fn hmac_bytes<M: Mac + hmac::digest::KeyInit>(key: &str, value: &str) -> Result<Vec<u8>, String> {
+    let mut mac = <M as Mac>::new_from_slice(key.as_bytes()).map_err(|e| e.to_string())?;
+    mac.update(value.as_bytes());
+    Ok(mac.finalize().into_bytes().to_vec())
+}
+
+fn encode_bytes(bytes: Vec<u8>, encoding: Option<String>) -> Result<String, String> {
+    match encoding.unwrap_or("hex".to_string()).as_str() {
+        "hex" => Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect()),
+        "base64" => Ok(base64::engine::general_purpose::STANDARD.encode(bytes)),
+        e => Err(format!("Unsupported encoding {e}")),
+    }
+}
+
+fn json_escape(args: &Args) -> Result<String, String> {
+    let value = required_str(args, "value")?;
+    let quoted = serde_json::to_string(&value).map_err(|e| e.to_string())?;
+    Ok(quoted[1..quoted.len() - 1].to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::native::{find_native_function, parse_offset};
+    use chrono::Duration;
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+
+    fn call(name: &str, args: Vec<(&str, Value)>) -> Result<String, String> {
+        let args = args
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect::<HashMap<String, Value>>();
+        find_native_function(name).unwrap().call(&args)
+    }
+
+    #[test]
+    fn random_functions_not_deterministic() {
+        let deterministic = |name| find_native_function(name).unwrap().deterministic;
+        assert!(!deterministic("uuid_v4"));
+        assert!(!deterministic("random_int"));
+        assert!(!deterministic("timestamp"));
+        assert!(deterministic("base64_encode"));
+        assert!(deterministic("hmac"));
+    }
+
+    #[test]
+    fn uuids() {
+        assert_eq!(call("uuid_v4", vec![]).unwrap().len(), 36);
+        assert_eq!(call("uuid_v7", vec![]).unwrap().chars().nth(14), Some('7'));
+    }
+
+    #[test]
+    fn timestamp_formats() {
+        let unix: i64 = call("timestamp", vec![("format", json!("unix"))])
+            .unwrap()
+            .parse()
+            .unwrap();
+        let yesterday: i64 = call(
+            "timestamp",
+            vec![("format", json!("unix")), ("offset", json!("-1d"))],
+        )
+        .unwrap()
+        .parse()
+        .unwrap();
+        assert!((unix - yesterday - 86400).abs() <= 1);
+        assert_eq!(
+            call("timestamp", vec![("format", json!("%Y"))])
+                .unwrap()
+                .len(),
+            4
+        );
+        assert!(call("timestamp", vec![("format", json!("%Q"))]).is_err());
+    }
+
+    #[test]
+    fn offsets() {
+        assert_eq!(
+            parse_offset("+2h30m"),
+            Ok(Duration::hours(2) + Duration::minutes(30))
+        );
+        assert_eq!(parse_offset("-1w"), Ok(-Duration::weeks(1)));
+        assert!(parse_offset("1y").is_err());
+    }
+
+    #[test]
+    fn random() {
+        assert_eq!(
+            call("random_int", vec![("min", json!(5)), ("max", json!("5"))]),
+            Ok("5".to_string())
+        );
+        assert!(call("random_int", vec![("min", json!(6)), ("max", json!(5))]).is_err());
+        assert_eq!(
+            call(
+                "random_string",
+                vec![("length", json!(3)), ("charset", json!("a"))]
+            ),
+            Ok("aaa".to_string())
+        );
+        assert_eq!(call("random_string", vec![]).unwrap().len(), 16);
+    }
+
+    #[test]
+    fn encoding() {
+        assert_eq!(
+            call("base64_encode", vec![("value", json!("hello"))]),
+            Ok("aGVsbG8=".to_string())
+        );
+        assert_eq!(
+            call("base64_decode", vec![("value", json!("aGVsbG8="))]),
+            Ok("hello".to_string())
+        );
+        assert_eq!(
+            call("url_encode", vec![("value", json!("a b&c"))]),
+            Ok("a%20b%26c".to_string())
+        );
+        assert_eq!(
+            call("url_decode", vec![("value", json!("a%20b%26c"))]),
+            Ok("a b&c".to_string())
+        );
+        assert_eq!(
+            call("json_escape", vec![("value", json!("say \"hi\"\n"))]),
+            Ok(r#"say \"hi\"\n"#.to_string())
+        );
+    }
+
+    #[test]
+    fn hashes() {
+        assert_eq!(
+            call(
+                "hash",
+                vec![("value", json!("abc")), ("algorithm", json!("md5"))]
+            ),
+            Ok("900150983cd24fb0d6963f7d28e17f72".to_string())
+        );
+        assert_eq!(
+            call("hash", vec![("value", json!("abc"))]),
+            Ok("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad".to_string())
+        );
+        assert_eq!(
+            call(
+                "hmac",
+                vec![
+                    (
+                        "value",
+                        json!("The quick brown fox jumps over the lazy dog")
+                    ),
+                    ("key", json!("key")),
+                    ("encoding", json!("hex")),
+                ]
+            ),
+            Ok("f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8".to_string())
+        );
+        assert!(call(
+            "hash",
+            vec![("value", json!("abc")), ("algorithm", json!("x"))]
+        )
+        .is_err());
+    }
+}
@@ -10,12 +10,20 @@ pub struct Tokens {
 
 impl Display for Tokens {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let str = self
-            .tokens
-            .iter()
-            .map(|t| t.to_string())
-            .collect::<Vec<String>>()
-            .join("");
+        let mut str = String::new();
+        for (i, t) in self.tokens.iter().enumerate() {
+            match t {
+                Token::Raw { text } => {
+                    // Backslashes right before a tag would escape it, so they need escaping too
+                    let before_tag = matches!(
+                        self.tokens.get(i + 1),
+                        Some(Token::Tag { .. } | Token::Escaped)
+                    );
+                    str.push_str(&escape_raw(text, before_tag));
+                }
+                t => str.push_str(&t.to_string()),
+            }
+        }
         write!(f, "{}", str)
     }
 }
@@ -29,11 +37,61 @@ pub struct FnArg {
 
 impl Display for FnArg {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let str = format!("{}={}", self.name, self.value);
+        // Positional args don't have a name
+        let str = if self.name.is_empty() {
+            self.value.to_string()
+        } else {
+            format!("{}={}", self.name, self.value)
+        };
+        write!(f, "{}", str)
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct Filter {
+    pub name: String,
+    pub args: Vec<FnArg>,
+}
+
+impl Display for Filter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str = if self.args.is_empty() {
+            self.name.to_string()
+        } else {
+            format!("{}({})", self.name, fn_args_to_string(&self.args))
+        };
         write!(f, "{}", str)
     }
 }
 
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ObjEntry {
+    pub key: String,
+    pub value: Val,
+}
+
+impl Display for ObjEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let key = Val::Str {
+            text: self.key.to_string(),
+        };
+        write!(f, "{}: {}", key, self.value)
+    }
+}
+
+fn fn_args_to_string(args: &[FnArg]) -> String {
+    // Unset named args are left out, but positional nulls have to stay to keep the others in place
+    args.iter()
+        .filter_map(|a| match a.value {
+            Val::Null if !a.name.is_empty() => None,
+            _ => Some(a.to_string()),
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize, TS)]
 #[serde(rename_all = "snake_case", tag = "type")]
 #[ts(export)]
@@ -41,28 +99,48 @@ pub enum Val {
     Str { text: String },
     Var { name: String },
     Bool { value: bool },
+    Num { value: f64 },
+    Arr { items: Vec<Val> },
+    Obj { entries: Vec<ObjEntry> },
     Fn { name: String, args: Vec<FnArg> },
+    Pipe { val: Box<Val>, filters: Vec<Filter> },
+    Coalesce { val: Box<Val>, fallback: Box<Val> },
     Null,
 }
 
 impl Display for Val {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let str = match self {
-            Val::Str { text } => format!("'{}'", text.to_string().replace("'", "\'")),
+            Val::Str { text } => format!("'{}'", text.replace('\\', "\\\\").replace('\'', "\\'")),
             Val::Var { name } => name.to_string(),
             Val::Bool { value } => value.to_string(),
-            Val::Fn { name, args } => {
-                format!(
-                    "{name}({})",
-                    args.iter()
-                        .filter_map(|a| match a.value.clone() {
-                            Val::Null => None,
-                            _ => Some(a.to_string()),
-                        })
-                        .collect::<Vec<String>>()
-                        .join(", ")
-                )
-            }
+            Val::Num { value } => value.to_string(),
+            Val::Arr { items } => format!(
+                "[{}]",
+                items
+                    .iter()
+                    .map(|v| v.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            Val::Obj { entries } => format!(
+                "{{{}}}",
+                entries
+                    .iter()
+                    .map(|e| e.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            Val::Fn { name, args } => format!("{name}({})", fn_args_to_string(args)),
+            Val::Pipe { val, filters } => format!(
+                "{val} | {}",
+                filters
+                    .iter()
+                    .map(|f| f.to_string())
+                    .collect::<Vec<String>>()
+                    .join(" | ")
+            ),
+            Val::Coalesce { val, fallback } => format!("{val} ?? {fallback}"),
             Val::Null => "null".to_string(),
         };
         write!(f, "{}", str)
@@ -73,15 +151,22 @@ impl Display for Val {
 #[serde(rename_all = "snake_case", tag = "type")]
 #[ts(export)]
 pub enum Token {
-    Raw { text: String },
-    Tag { val: Val },
+    Raw {
+        text: String,
+    },
+    /// An escaped `\${[`, which renders as a literal `${[`
+    Escaped,
+    Tag {
+        val: Val,
+    },
     Eof,
 }
 
 impl Display for Token {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let str = match self {
-            Token::Raw { text } => text.to_string(),
+            Token::Raw { text } => escape_raw(text, false),
+            Token::Escaped => "\\${[".to_string(),
             Token::Tag { val } => format!("${{[ {} ]}}", val.to_string()),
             Token::Eof => "".to_string(),
         };
@@ -89,12 +174,36 @@ impl Display for Token {
     }
 }
 
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ParseError {
+    pub message: String,
+    /// Character offset where the error starts
+    pub start: usize,
+    /// Character offset where the error ends (exclusive)
+    pub end: usize,
+    pub expected: Option<String>,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at position {}", self.message, self.start)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
 // Template Syntax
 //
 //  ${[ my_var ]}
 //  ${[ my_fn() ]}
 //  ${[ my_fn(my_var) ]}
 //  ${[ my_fn(my_var, "A String") ]}
+//  ${[ my_var | my_filter | my_fn('positional', a=my_var) ]}
+//  ${[ my_fn(count=-1.5, list=[1, 'two'], obj={'a': true, b: null}) ]}
+//  ${[ my_var ?? my_other_var ?? 'default' ]}
+//  \${[ not_a_tag ]}
+//  \\${[ backslash_then_tag ]}
 
 // default
 #[derive(Default)]
@@ -103,6 +212,10 @@ pub struct Parser {
     chars: Vec<char>,
     pos: usize,
     curr_text: String,
+    errors: Vec<ParseError>,
+    // Furthest failure seen while parsing the current tag
+    tag_error: Option<ParseError>,
+    tag_positions: Vec<usize>,
 }
 
 impl Parser {
@@ -117,11 +230,18 @@ impl Parser {
         let start_pos = self.pos;
 
         while self.pos < self.chars.len() {
-            if self.match_str("${[") {
+            if self.peek_char() == '\\' {
+                self.parse_backslashes();
+            } else if self.match_str("${[") {
                 let start_curr = self.pos;
+                self.tag_error = None;
                 if let Some(t) = self.parse_tag() {
+                    self.tag_positions.push(start_curr - "${[".len());
                     self.push_token(t);
                 } else {
+                    if let Some(e) = self.tag_error.take() {
+                        self.errors.push(e);
+                    }
                     self.pos = start_curr;
                     self.curr_text += "${[";
                 }
@@ -141,20 +261,45 @@ impl Parser {
         }
     }
 
+    /// Parse the template, but fail with every malformed tag instead of
+    /// falling back to raw text.
+    pub fn parse_strict(&mut self) -> Result<Tokens, Vec<ParseError>> {
+        let tokens = self.parse();
+        if self.errors.is_empty() {
+            Ok(tokens)
+        } else {
+            Err(self.errors.clone())
+        }
+    }
+
+    /// Errors for the tags that failed to parse during the last `parse()`
+    pub fn errors(&self) -> &[ParseError] {
+        &self.errors
+    }
+
+    /// Character offset of each parsed tag, in the same order as the tag tokens
+    pub fn tag_positions(&self) -> &[usize] {
+        &self.tag_positions
+    }
+
     fn parse_tag(&mut self) -> Option<Token> {
         // Parse up to first identifier
         //    ${[ my_var...
         self.skip_whitespace();
 
-        let val = match self.parse_value() {
+        let val = match self.parse_expr() {
             Some(v) => v,
-            None => return None,
+            None => {
+                self.fail("value");
+                return None;
+            }
         };
 
         // Parse to closing tag
         //    ${[ my_var(a, b, c) ]}
         self.skip_whitespace();
         if !self.match_str("]}") {
+            self.fail("]}");
             return None;
         }
 
@@ -172,8 +317,77 @@ impl Parser {
         );
     }
 
+    fn parse_expr(&mut self) -> Option<Val> {
+        let val = self.parse_coalesce()?;
+
+        // Parse any filters that the value is piped through
+        //    ${[ my_var | my_filter | my_fn(a) ]}
+        let mut filters = Vec::new();
+        loop {
+            let start_pos = self.pos;
+            self.skip_whitespace();
+            if !self.match_str("|") {
+                self.pos = start_pos;
+                break;
+            }
+
+            self.skip_whitespace();
+            if let Some((name, args)) = self.parse_fn() {
+                filters.push(Filter { name, args });
+            } else if let Some(name) = self.parse_ident() {
+                filters.push(Filter {
+                    name,
+                    args: Vec::new(),
+                });
+            } else {
+                self.fail("filter name");
+                return None;
+            }
+        }
+
+        if filters.is_empty() {
+            Some(val)
+        } else {
+            Some(Val::Pipe {
+                val: Box::new(val),
+                filters,
+            })
+        }
+    }
+
+    fn parse_coalesce(&mut self) -> Option<Val> {
+        let val = self.parse_value()?;
+
+        // Parse the fallback used when the value is undefined or empty
+        //    ${[ my_var ?? 'default' ]}
+        let start_pos = self.pos;
+        self.skip_whitespace();
+        if !self.match_str("??") {
+            self.pos = start_pos;
+            return Some(val);
+        }
+
+        self.skip_whitespace();
+        match self.parse_coalesce() {
+            Some(fallback) => Some(Val::Coalesce {
+                val: Box::new(val),
+                fallback: Box::new(fallback),
+            }),
+            None => {
+                self.fail("fallback value");
+                None
+            }
+        }
+    }
+
     fn parse_value(&mut self) -> Option<Val> {
-        if let Some((name, args)) = self.parse_fn() {
+        if let Some(v) = self.parse_number() {
+            Some(Val::Num { value: v })
+        } else if let Some(items) = self.parse_array() {
+            Some(Val::Arr { items })
+        } else if let Some(entries) = self.parse_object() {
+            Some(Val::Obj { entries })
+        } else if let Some((name, args)) = self.parse_fn() {
             Some(Val::Fn { name, args })
         } else if let Some(v) = self.parse_ident() {
             if v == "null" {
@@ -232,20 +446,38 @@ impl Parser {
         while self.pos < self.chars.len() {
             self.skip_whitespace();
 
-            let name = self.parse_ident();
-            self.skip_whitespace();
-            self.match_str("=");
+            // Args are named (a=value) unless there's no = after the identifier
+            let arg_start = self.pos;
+            let name = match self.parse_ident() {
+                Some(name) => {
+                    self.skip_whitespace();
+                    if self.match_str("=") {
+                        Some(name)
+                    } else {
+                        self.pos = arg_start;
+                        None
+                    }
+                }
+                None => None,
+            };
             self.skip_whitespace();
-            let value = self.parse_value();
+            let value = match self.parse_expr() {
+                Some(value) => value,
+                None => {
+                    self.fail(match name {
+                        Some(_) => "argument value",
+                        None => "argument",
+                    });
+                    self.pos = start_pos;
+                    return None;
+                }
+            };
             self.skip_whitespace();
 
-            if let (Some(name), Some(value)) = (name.clone(), value.clone()) {
-                args.push(FnArg { name, value });
-            } else {
-                // Didn't find valid thing, so return
-                self.pos = start_pos;
-                return None;
-            }
+            args.push(FnArg {
+                name: name.unwrap_or_default(),
+                value,
+            });
 
             if self.match_str(")") {
                 break;
@@ -255,6 +487,7 @@ impl Parser {
 
             // If we don't find a comma, that's bad
             if !args.is_empty() && !self.match_str(",") {
+                self.fail("',' or ')'");
                 self.pos = start_pos;
                 return None;
             }
@@ -267,13 +500,150 @@ impl Parser {
         Some(args)
     }
 
+    fn parse_number(&mut self) -> Option<f64> {
+        let start_pos = self.pos;
+
+        let mut text = String::new();
+        if self.match_str("-") {
+            text.push('-');
+        }
+
+        let mut seen_dot = false;
+        while self.pos < self.chars.len() {
+            let ch = self.peek_char();
+            if ch.is_ascii_digit() {
+                text.push(ch);
+            } else if ch == '.' && !seen_dot && self.peek_is_digit(1) {
+                seen_dot = true;
+                text.push(ch);
+            } else {
+                break;
+            }
+            self.pos += 1;
+        }
+
+        // Things like 1abc are identifiers, not numbers
+        let followed_by_ident = self.pos < self.chars.len() && is_ident_char(self.peek_char());
+        if followed_by_ident || !text.ends_with(|c: char| c.is_ascii_digit()) {
+            self.pos = start_pos;
+            return None;
+        }
+
+        match text.parse::<f64>() {
+            Ok(v) => Some(v),
+            Err(_) => {
+                self.pos = start_pos;
+                None
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Option<Vec<Val>> {
+        if !self.match_str("[") {
+            return None;
+        }
+
+        let start_pos = self.pos;
+        let mut items = Vec::new();
+
+        self.skip_whitespace();
+        if self.match_str("]") {
+            return Some(items);
+        }
+
+        loop {
+            self.skip_whitespace();
+            match self.parse_expr() {
+                Some(v) => items.push(v),
+                None => {
+                    self.fail("value");
+                    self.pos = start_pos - 1;
+                    return None;
+                }
+            };
+            self.skip_whitespace();
+
+            if self.match_str("]") {
+                break;
+            } else if !self.match_str(",") {
+                self.fail("',' or ']'");
+                self.pos = start_pos - 1;
+                return None;
+            }
+        }
+
+        Some(items)
+    }
+
+    fn parse_object(&mut self) -> Option<Vec<ObjEntry>> {
+        if !self.match_str("{") {
+            return None;
+        }
+
+        let start_pos = self.pos;
+        let mut entries = Vec::new();
+
+        self.skip_whitespace();
+        if self.match_str("}") {
+            return Some(entries);
+        }
+
+        loop {
+            self.skip_whitespace();
+            let key = match self.parse_string().or_else(|| self.parse_ident()) {
+                Some(k) => k,
+                None => {
+                    self.fail("object key");
+                    self.pos = start_pos - 1;
+                    return None;
+                }
+            };
+
+            self.skip_whitespace();
+            if !self.match_str(":") {
+                self.fail("':'");
+                self.pos = start_pos - 1;
+                return None;
+            }
+
+            self.skip_whitespace();
+            let value = match self.parse_expr() {
+                Some(v) => v,
+                None => {
+                    self.fail("value");
+                    self.pos = start_pos - 1;
+                    return None;
+                }
+            };
+            entries.push(ObjEntry { key, value });
+            self.skip_whitespace();
+
+            if self.match_str("}") {
+                break;
+            } else if !self.match_str(",") {
+                self.fail("',' or '}'");
+                self.pos = start_pos - 1;
+                return None;
+            }
+        }
+
+        Some(entries)
+    }
+
     fn parse_ident(&mut self) -> Option<String> {
         let start_pos = self.pos;
 
         let mut text = String::new();
         while self.pos < self.chars.len() {
             let ch = self.peek_char();
-            if ch.is_alphanumeric() || ch == '_' {
+            // Dots separate namespaced names like `env.HOME`, but can't start or end one
+            let is_separator = ch == '.'
+                && !text.is_empty()
+                && self
+                    .chars
+                    .get(self.pos + 1)
+                    .is_some_and(|c| is_ident_char(*c));
+            if is_ident_char(ch) || is_separator {
                 text.push(ch);
                 self.pos += 1;
             } else {
@@ -305,7 +675,7 @@ impl Parser {
         while self.pos < self.chars.len() {
             let ch = self.next_char();
             match ch {
-                '\\' => {
+                '\\' if self.pos < self.chars.len() => {
                     text.push(self.next_char());
                 }
                 '\'' => {
@@ -323,6 +693,7 @@ impl Parser {
         }
 
         if !found_closing {
+            self.fail("closing quote");
             self.pos = start_pos;
             return None;
         }
@@ -330,6 +701,34 @@ impl Parser {
         Some(text)
     }
 
+    /// Record that `expected` was not found at the current position. Only the
+    /// furthest failure within a tag is kept, since that's the most useful one.
+    fn fail(&mut self, expected: &str) {
+        if let Some(e) = &self.tag_error {
+            if e.start >= self.pos {
+                return;
+            }
+        }
+
+        let (message, end) = match self.chars.get(self.pos) {
+            Some(ch) => (
+                format!("Expected {expected} but found '{ch}'"),
+                self.pos + 1,
+            ),
+            None => (
+                format!("Expected {expected} but reached end of template"),
+                self.pos,
+            ),
+        };
+
+        self.tag_error = Some(ParseError {
+            message,
+            start: self.pos,
+            end,
+            expected: Some(expected.to_string()),
+        });
+    }
+
     fn skip_whitespace(&mut self) {
         while self.pos < self.chars.len() {
             if self.peek_char().is_whitespace() {
@@ -347,11 +746,41 @@ impl Parser {
         ch
     }
 
+    fn peek_is_digit(&self, offset: usize) -> bool {
+        match self.chars.get(self.pos + offset) {
+            Some(ch) => ch.is_ascii_digit(),
+            None => false,
+        }
+    }
+
     fn peek_char(&self) -> char {
         let ch = self.chars[self.pos];
         ch
     }
 
+    /// Backslashes before a tag opener escape each other in pairs, and a leftover one
+    /// escapes the opener. Anywhere else they're just text.
+    fn parse_backslashes(&mut self) {
+        let start = self.pos;
+        while self.pos < self.chars.len() && self.peek_char() == '\\' {
+            self.pos += 1;
+        }
+
+        let count = self.pos - start;
+        if !self.match_str("${[") {
+            self.curr_text += &"\\".repeat(count);
+            return;
+        }
+
+        self.curr_text += &"\\".repeat(count / 2);
+        if count % 2 == 1 {
+            self.push_token(Token::Escaped);
+        } else {
+            // Leave the opener to be parsed as a tag
+            self.pos -= "${[".len();
+        }
+    }
+
     fn push_token(&mut self, token: Token) {
         // Push any text we've accumulated
         if !self.curr_text.is_empty() {
@@ -384,6 +813,55 @@ impl Parser {
     }
 }
 
+/// Escape the text so it renders as-is, even if it contains tags
+pub fn escape_template(text: &str) -> String {
+    let mut tokens = Vec::new();
+    for (i, part) in text.split("${[").enumerate() {
+        if i > 0 {
+            tokens.push(Token::Escaped);
+        }
+        if !part.is_empty() {
+            tokens.push(Token::Raw {
+                text: part.to_string(),
+            });
+        }
+    }
+    Tokens { tokens }.to_string()
+}
+
+/// Write raw text back out so it parses to the same text. Backslashes are doubled where
+/// they'd otherwise escape a tag opener, either one in the text or the tag that follows it.
+fn escape_raw(text: &str, before_tag: bool) -> String {
+    let mut out = String::new();
+    let mut backslashes = 0;
+    for (i, ch) in text.char_indices() {
+        if ch == '\\' {
+            backslashes += 1;
+            continue;
+        }
+        let escaped = backslashes > 0 && text[i..].starts_with("${[");
+        let count = if escaped {
+            backslashes * 2
+        } else {
+            backslashes
+        };
+        out += &"\\".repeat(count);
+        backslashes = 0;
+        out.push(ch);
+    }
+    let count = if before_tag {
+        backslashes * 2
+    } else {
+        backslashes
+    };
+    out += &"\\".repeat(count);
+    out
+}
+
+fn is_ident_char(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_'
+}
+
 #[cfg(test)]
 mod tests {
     use crate::Val::Null;
@@ -420,6 +898,25 @@ mod tests {
         );
     }
 
+    #[test]
+    fn var_dotted() {
+        let mut p = Parser::new("${[ env.HOME ]}${[ foo. ]}");
+        assert_eq!(
+            p.parse().tokens,
+            vec![
+                Token::Tag {
+                    val: Val::Var {
+                        name: "env.HOME".into()
+                    }
+                },
+                Token::Raw {
+                    text: "${[ foo. ]}".into()
+                },
+                Token::Eof
+            ]
+        );
+    }
+
     #[test]
     fn var_multiple_names_invalid() {
         let mut p = Parser::new("${[ foo bar ]}");
@@ -634,6 +1131,266 @@ mod tests {
         );
     }
 
+    #[test]
+    fn pipe_filters() {
+        let mut p = Parser::new("${[ body | json_path('$.id') | upper ]}");
+        assert_eq!(
+            p.parse().tokens,
+            vec![
+                Token::Tag {
+                    val: Val::Pipe {
+                        val: Box::new(Val::Var {
+                            name: "body".into()
+                        }),
+                        filters: vec![
+                            Filter {
+                                name: "json_path".into(),
+                                args: vec![FnArg {
+                                    name: "".into(),
+                                    value: Val::Str {
+                                        text: "$.id".into()
+                                    }
+                                }],
+                            },
+                            Filter {
+                                name: "upper".into(),
+                                args: vec![],
+                            },
+                        ],
+                    }
+                },
+                Token::Eof
+            ]
+        );
+    }
+
+    #[test]
+    fn pipe_in_fn_arg() {
+        let mut p = Parser::new("${[ foo(a=bar|upper, baz) ]}");
+        assert_eq!(
+            p.parse().tokens,
+            vec![
+                Token::Tag {
+                    val: Val::Fn {
+                        name: "foo".into(),
+                        args: vec![
+                            FnArg {
+                                name: "a".into(),
+                                value: Val::Pipe {
+                                    val: Box::new(Val::Var { name: "bar".into() }),
+                                    filters: vec![Filter {
+                                        name: "upper".into(),
+                                        args: vec![],
+                                    }],
+                                },
+                            },
+                            FnArg {
+                                name: "".into(),
+                                value: Val::Var { name: "baz".into() },
+                            },
+                        ],
+                    }
+                },
+                Token::Eof
+            ]
+        );
+    }
+
+    #[test]
+    fn pipe_missing_filter() {
+        let mut p = Parser::new("${[ foo | ]}");
+        assert_eq!(
+            p.parse_strict().unwrap_err()[0].expected,
+            Some("filter name".to_string())
+        );
+    }
+
+    #[test]
+    fn pipe_round_trip() {
+        let template = "${[ token | base64 ]} ${[ body | json_path('$.id', a=b) | upper ]}";
+        let tokens = Parser::new(template).parse();
+        assert_eq!(tokens.to_string(), template);
+    }
+
+    #[test]
+    fn literals() {
+        let mut p = Parser::new("${[ foo(a=-1.5, b=[1, bar], c={'x': 'y', z: []}) ]}");
+        assert_eq!(
+            p.parse().tokens,
+            vec![
+                Token::Tag {
+                    val: Val::Fn {
+                        name: "foo".into(),
+                        args: vec![
+                            FnArg {
+                                name: "a".into(),
+                                value: Val::Num { value: -1.5 },
+                            },
+                            FnArg {
+                                name: "b".into(),
+                                value: Val::Arr {
+                                    items: vec![
+                                        Val::Num { value: 1.0 },
+                                        Val::Var { name: "bar".into() },
+                                    ]
+                                },
+                            },
+                            FnArg {
+                                name: "c".into(),
+                                value: Val::Obj {
+                                    entries: vec![
+                                        ObjEntry {
+                                            key: "x".into(),
+                                            value: Val::Str { text: "y".into() },
+                                        },
+                                        ObjEntry {
+                                            key: "z".into(),
+                                            value: Val::Arr { items: vec![] },
+                                        },
+                                    ]
+                                },
+                            },
+                        ],
+                    }
+                },
+                Token::Eof
+            ]
+        );
+    }
+
+    #[test]
+    fn number_like_ident() {
+        let mut p = Parser::new("${[ 1abc ]}");
+        assert_eq!(
+            p.parse().tokens,
+            vec![
+                Token::Tag {
+                    val: Val::Var {
+                        name: "1abc".into()
+                    }
+                },
+                Token::Eof
+            ]
+        );
+    }
+
+    #[test]
+    fn literals_round_trip() {
+        let template = "${[ foo(a=-1.5, b=[1, bar], c={'x': 'y', 'z': []}) ]}";
+        let tokens = Parser::new(template).parse();
+        assert_eq!(tokens.to_string(), template);
+    }
+
+    #[test]
+    fn unclosed_array() {
+        let mut p = Parser::new("${[ [1 2] ]}");
+        assert_eq!(
+            p.parse_strict().unwrap_err()[0].expected,
+            Some("',' or ']'".to_string())
+        );
+    }
+
+    #[test]
+    fn coalesce() {
+        let mut p = Parser::new("${[ a ?? b() ?? 'c' | upper ]}");
+        assert_eq!(
+            p.parse().tokens,
+            vec![
+                Token::Tag {
+                    val: Val::Pipe {
+                        val: Box::new(Val::Coalesce {
+                            val: Box::new(Val::Var { name: "a".into() }),
+                            fallback: Box::new(Val::Coalesce {
+                                val: Box::new(Val::Fn {
+                                    name: "b".into(),
+                                    args: vec![],
+                                }),
+                                fallback: Box::new(Val::Str { text: "c".into() }),
+                            }),
+                        }),
+                        filters: vec![Filter {
+                            name: "upper".into(),
+                            args: vec![],
+                        }],
+                    }
+                },
+                Token::Eof
+            ]
+        );
+    }
+
+    #[test]
+    fn coalesce_round_trip() {
+        let template = "${[ api_host ?? 'localhost:8080' ]}";
+        let tokens = Parser::new(template).parse();
+        assert_eq!(tokens.to_string(), template);
+    }
+
+    #[test]
+    fn coalesce_missing_fallback() {
+        let mut p = Parser::new("${[ a ?? ]}");
+        assert_eq!(
+            p.parse_strict().unwrap_err()[0].expected,
+            Some("fallback value".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_strict_valid() {
+        let mut p = Parser::new("Hello ${[ foo(a='b') ]}");
+        assert!(p.parse_strict().is_ok());
+    }
+
+    #[test]
+    fn parse_strict_missing_arg_value() {
+        let mut p = Parser::new("${[ my_fn(a=) ]}");
+        assert_eq!(
+            p.parse_strict(),
+            Err(vec![ParseError {
+                message: "Expected argument value but found ')'".into(),
+                start: 12,
+                end: 13,
+                expected: Some("argument value".into()),
+            }])
+        );
+    }
+
+    #[test]
+    fn parse_strict_unclosed_tag() {
+        let mut p = Parser::new("${[ foo ");
+        assert_eq!(
+            p.parse_strict(),
+            Err(vec![ParseError {
+                message: "Expected ]} but reached end of template".into(),
+                start: 8,
+                end: 8,
+                expected: Some("]}".into()),
+            }])
+        );
+    }
+
+    #[test]
+    fn parse_errors_multiple() {
+        let mut p = Parser::new("${[ ]} and ${[ foo(a='x) ]}");
+        let tokens = p.parse();
+        assert_eq!(
+            tokens.tokens,
+            vec![
+                Token::Raw {
+                    text: "${[ ]} and ${[ foo(a='x) ]}".into()
+                },
+                Token::Eof
+            ]
+        );
+        assert_eq!(
+            p.errors()
+                .iter()
+                .map(|e| (e.start, e.expected.clone().unwrap()))
+                .collect::<Vec<(usize, String)>>(),
+            vec![(4, "value".to_string()), (27, "closing quote".to_string())]
+        );
+    }
+
     #[test]
     fn token_display_var() {
         assert_eq!(
@@ -652,10 +1409,118 @@ mod tests {
                 text: "Hello 'You'".to_string()
             }
             .to_string(),
-            "'Hello \'You\''"
+            r#"'Hello \'You\''"#
+        );
+    }
+
+    #[test]
+    fn token_display_str_backslash() {
+        assert_eq!(
+            Val::Str {
+                text: r#"C:\dir\"#.to_string()
+            }
+            .to_string(),
+            r#"'C:\\dir\\'"#
+        );
+    }
+
+    #[test]
+    fn escaped_tag() {
+        let mut p = Parser::new(r#"\${[ foo ]} ${[ bar ]}"#);
+        assert_eq!(
+            p.parse().tokens,
+            vec![
+                Token::Escaped,
+                Token::Raw {
+                    text: " foo ]} ".into()
+                },
+                Token::Tag {
+                    val: Val::Var { name: "bar".into() }
+                },
+                Token::Eof
+            ]
+        );
+    }
+
+    #[test]
+    fn escaped_backslash_before_tag() {
+        let mut p = Parser::new(r#"C:\\${[ dir ]} \\\${[ x ]} a\\${[ y ]}"#);
+        assert_eq!(
+            p.parse().tokens,
+            vec![
+                Token::Raw {
+                    text: r#"C:\"#.into()
+                },
+                Token::Tag {
+                    val: Val::Var { name: "dir".into() }
+                },
+                Token::Raw {
+                    text: r#" \"#.into()
+                },
+                Token::Escaped,
+                Token::Raw {
+                    text: r#" x ]} a\"#.into()
+                },
+                Token::Tag {
+                    val: Val::Var { name: "y".into() }
+                },
+                Token::Eof
+            ]
         );
     }
 
+    #[test]
+    fn escaped_tag_round_trip() {
+        let template = r#"{"a": "\${[ foo ]}"} ${[ fn(a='it\'s \\ ok') ]}"#;
+        let tokens = Parser::new(template).parse();
+        assert_eq!(tokens.to_string(), template);
+        assert_eq!(Parser::new(&tokens.to_string()).parse(), tokens);
+    }
+
+    #[test]
+    fn escape_template_text() {
+        for text in [
+            r#"${[ a ]}"#,
+            r#"x\${[ a ]}\"#,
+            r#"\\${[ ${[ a ]} ]}"#,
+            "plain",
+        ] {
+            let tokens = Parser::new(&escape_template(text)).parse();
+            let rendered = tokens
+                .tokens
+                .iter()
+                .map(|t| match t {
+                    Token::Raw { text } => text.as_str(),
+                    Token::Escaped => "${[",
+                    Token::Eof => "",
+                    _ => panic!("Unexpected token {t:?}"),
+                })
+                .collect::<String>();
+            assert_eq!(rendered, text);
+        }
+    }
+
+    #[test]
+    fn failed_tag_round_trip() {
+        let template = r#"${[ ]} and \\${[ foo(a='x) ]} \\"#;
+        let tokens = Parser::new(template).parse();
+        assert_eq!(tokens.to_string(), template);
+        assert_eq!(Parser::new(&tokens.to_string()).parse(), tokens);
+    }
+
+    #[test]
+    fn backslash_round_trip() {
+        for template in [
+            r#"C:\\${[ dir ]}\\file"#,
+            r#"\\\${[ not_a_tag ]} \\${[ tag ]}"#,
+            r#"\\\\${[ tag ]} \\ \\\\"#,
+        ] {
+            let tokens = Parser::new(template).parse();
+            assert_eq!(tokens.to_string(), template);
+            assert_eq!(Parser::new(&tokens.to_string()).parse(), tokens);
+        }
+    }
+
     #[test]
     fn token_null_fn_arg() {
         assert_eq!(
@@ -679,6 +1544,17 @@ mod tests {
         );
     }
 
+    #[test]
+    fn token_null_positional_fn_arg() {
+        let template = "${[ fn(null, 'b', c=null) ]}";
+        let tokens = Parser::new(template).parse();
+        assert_eq!(tokens.to_string(), "${[ fn(null, 'b') ]}");
+        assert_eq!(
+            Parser::new(&tokens.to_string()).parse(),
+            Parser::new("${[ fn(null, 'b') ]}").parse()
+        );
+    }
+
     #[test]
     fn token_display_fn() {
         assert_eq!(
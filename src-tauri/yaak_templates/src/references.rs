@@ -0,0 +1,274 @@
+use crate::{FnArg, Parser, Token, Tokens, Val};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use ts_rs::TS;
+
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct TemplateReference {
+    pub name: String,
+    /// Where the reference appears, like `url` or `headers.Authorization`
+    pub locations: Vec<String>,
+    /// Whether a variable or function with this name exists
+    pub defined: bool,
+}
+
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct TemplateReferences {
+    pub variables: Vec<TemplateReference>,
+    pub functions: Vec<TemplateReference>,
+    /// Defined variables that nothing references, directly or through other variables
+    pub unused_variables: Vec<String>,
+}
+
+/// Where a value used by a tag came from
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize, TS)]
+#[serde(rename_all = "snake_case", tag = "type")]
+#[ts(export)]
+pub enum TagSource {
+    Workspace {
+        workspace_id: String,
+        workspace_name: String,
+        variable: String,
+    },
+    Environment {
+        environment_id: String,
+        environment_name: String,
+        variable: String,
+    },
+    Folder {
+        folder_id: String,
+        folder_name: String,
+        variable: String,
+    },
+    /// A variable defined on the request itself
+    Request {
+        variable: String,
+    },
+    Plugin {
+        plugin: String,
+        function: String,
+    },
+    Native {
+        function: String,
+    },
+    /// An `env.NAME` variable read from the process environment
+    ProcessEnv {
+        variable: String,
+    },
+    /// A variable from a `.env` file attached to an environment
+    EnvFile {
+        environment_id: String,
+        environment_name: String,
+        path: String,
+        variable: String,
+    },
+    /// A variable or function that isn't defined anywhere
+    Unknown {
+        name: String,
+    },
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct TagProvenance {
+    /// The tag's expression, without the surrounding `${[ ]}`
+    pub tag: String,
+    /// Character offset of the tag in the template
+    pub position: usize,
+    pub value: String,
+    pub sources: Vec<TagSource>,
+    /// How each of the tag's variables was resolved, as one path per chain of variables
+    /// referencing each other, like `a` → `b` → `env.HOME`
+    pub resolution_paths: Vec<Vec<TagSource>>,
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct TemplateProvenance {
+    pub value: String,
+    pub tags: Vec<TagProvenance>,
+}
+
+#[derive(Default)]
+struct Names {
+    variables: Vec<String>,
+    functions: Vec<String>,
+}
+
+fn push_unique(names: &mut Vec<String>, name: &str) {
+    if !names.iter().any(|n| n == name) {
+        names.push(name.to_string());
+    }
+}
+
+/// Names of all variables referenced by the tokens, in order of first use
+pub fn referenced_variables(tokens: &Tokens) -> Vec<String> {
+    collect_names(tokens).variables
+}
+
+/// Names of all functions (including filters) called by the tokens, in order of first use
+pub fn referenced_functions(tokens: &Tokens) -> Vec<String> {
+    collect_names(tokens).functions
+}
+
+fn collect_names(tokens: &Tokens) -> Names {
+    let mut names = Names::default();
+    for t in &tokens.tokens {
+        if let Token::Tag { val } = t {
+            collect_val_names(val, &mut names);
+        }
+    }
+    names
+}
+
+fn collect_val_names(val: &Val, names: &mut Names) {
+    match val {
+        Val::Var { name } => push_unique(&mut names.variables, name),
+        Val::Arr { items } => items.iter().for_each(|v| collect_val_names(v, names)),
+        Val::Obj { entries } => entries
+            .iter()
+            .for_each(|e| collect_val_names(&e.value, names)),
+        Val::Fn { name, args } => {
+            push_unique(&mut names.functions, name);
+            collect_args_names(args, names);
+        }
+        Val::Pipe { val, filters } => {
+            collect_val_names(val, names);
+            for f in filters {
+                push_unique(&mut names.functions, &f.name);
+                collect_args_names(&f.args, names);
+            }
+        }
+        Val::Coalesce { val, fallback } => {
+            collect_val_names(val, names);
+            collect_val_names(fallback, names);
+        }
+        Val::Str { .. } | Val::Bool { .. } | Val::Num { .. } | Val::Null => {}
+    }
+}
+
+fn collect_args_names(args: &[FnArg], names: &mut Names) {
+    args.iter().for_each(|a| collect_val_names(&a.value, names));
+}
+
+fn add_reference(refs: &mut Vec<TemplateReference>, name: &str, location: &str, defined: bool) {
+    match refs.iter_mut().find(|r| r.name == name) {
+        Some(r) => push_unique(&mut r.locations, location),
+        None => refs.push(TemplateReference {
+            name: name.to_string(),
+            locations: vec![location.to_string()],
+            defined,
+        }),
+    }
+}
+
+/// Find the variables and functions used by a set of `(location, template)` pairs, and
+/// which of the given variables aren't used by any of them
+pub fn analyze_references(
+    templates: &[(String, String)],
+    vars: &HashMap<String, String>,
+    functions: &[String],
+) -> TemplateReferences {
+    let mut references = TemplateReferences::default();
+    for (location, template) in templates {
+        let names = collect_names(&Parser::new(template).parse());
+        for name in names.variables {
+            let defined = vars.contains_key(&name);
+            add_reference(&mut references.variables, &name, location, defined);
+        }
+        for name in names.functions {
+            let defined = functions.contains(&name);
+            add_reference(&mut references.functions, &name, location, defined);
+        }
+    }
+
+    // Variables used by other variables count as used, as long as something uses those
+    let mut used: Vec<String> = Vec::new();
+    let mut to_visit: Vec<String> = references
+        .variables
+        .iter()
+        .map(|r| r.name.to_string())
+        .collect();
+    while let Some(name) = to_visit.pop() {
+        if used.contains(&name) {
+            continue;
+        }
+        if let Some(value) = vars.get(&name) {
+            to_visit.extend(referenced_variables(&Parser::new(value).parse()));
+        }
+        used.push(name);
+    }
+
+    let mut unused: Vec<String> = vars.keys().filter(|k| !used.contains(k)).cloned().collect();
+    unused.sort();
+    references.unused_variables = unused;
+
+    references
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn referenced() {
+        let tokens = Parser::new("${[ a ]} ${[ f(x=b, y=[c]) | g(a) ]} ${[ d ?? e ]}").parse();
+        assert_eq!(referenced_variables(&tokens), vec!["a", "b", "c", "d", "e"]);
+        assert_eq!(referenced_functions(&tokens), vec!["f", "g"]);
+    }
+
+    #[test]
+    fn analyze() {
+        let templates = vec![
+            ("url".to_string(), "${[ base_url ]}/users".to_string()),
+            (
+                "headers.Authorization".to_string(),
+                "Bearer ${[ token ?? missing ]}".to_string(),
+            ),
+            (
+                "body.text".to_string(),
+                "${[ uuid_v4() ]} ${[ base_url ]}".to_string(),
+            ),
+        ];
+        let vars = HashMap::from([
+            ("base_url".to_string(), "${[ host ]}".to_string()),
+            ("host".to_string(), "localhost".to_string()),
+            ("token".to_string(), "abc".to_string()),
+            ("stale".to_string(), "old".to_string()),
+        ]);
+        let result = analyze_references(&templates, &vars, &["uuid_v4".to_string()]);
+
+        assert_eq!(
+            result.variables,
+            vec![
+                TemplateReference {
+                    name: "base_url".to_string(),
+                    locations: vec!["url".to_string(), "body.text".to_string()],
+                    defined: true,
+                },
+                TemplateReference {
+                    name: "token".to_string(),
+                    locations: vec!["headers.Authorization".to_string()],
+                    defined: true,
+                },
+                TemplateReference {
+                    name: "missing".to_string(),
+                    locations: vec!["headers.Authorization".to_string()],
+                    defined: false,
+                },
+            ]
+        );
+        assert_eq!(
+            result.functions,
+            vec![TemplateReference {
+                name: "uuid_v4".to_string(),
+                locations: vec!["body.text".to_string()],
+                defined: true,
+            }]
+        );
+        assert_eq!(result.unused_variables, vec!["stale".to_string()]);
+    }
+}
@@ -1,5 +1,8 @@
-use crate::{FnArg, Parser, Token, Tokens, Val};
+use crate::error;
+use crate::error::Error::{FunctionErr, ParseErr, VariableNotDefinedErr};
+use crate::{referenced_functions, referenced_variables, FnArg, Parser, Token, Tokens, Val};
 use log::warn;
+use serde_json::{Map, Value};
 use std::collections::HashMap;
 use std::future::Future;
 
@@ -7,92 +10,294 @@ pub trait TemplateCallback {
     fn run(
         &self,
         fn_name: &str,
-        args: HashMap<String, String>,
+        args: HashMap<String, Value>,
     ) -> impl Future<Output = Result<String, String>> + Send;
+
+    /// Names of the function's arguments, in order. Used to resolve positional
+    /// arguments, including the value piped into a filter.
+    fn arg_names(&self, _fn_name: &str) -> impl Future<Output = Vec<String>> + Send {
+        async { Vec::new() }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct RenderOptions {
+    /// Fail on malformed tags, undefined variables, and function errors instead of
+    /// rendering them as raw text or empty strings
+    pub strict: bool,
+}
+
+impl RenderOptions {
+    pub fn strict() -> RenderOptions {
+        RenderOptions { strict: true }
+    }
 }
 
 pub async fn parse_and_render<T: TemplateCallback>(
     template: &str,
     vars: &HashMap<String, String>,
     cb: &T,
-) -> String {
+    opt: &RenderOptions,
+) -> error::Result<String> {
+    let mut p = Parser::new(template);
+    let tokens = if opt.strict {
+        p.parse_strict().map_err(|e| ParseErr(e[0].clone()))?
+    } else {
+        p.parse()
+    };
+    render_tokens(tokens, p.tag_positions(), vars, cb, opt, None).await
+}
+
+/// A tag and the value it rendered to
+#[derive(Clone, PartialEq, Debug)]
+pub struct RenderedTag {
+    /// The tag's expression, without the surrounding `${[ ]}`
+    pub tag: String,
+    pub position: usize,
+    pub value: String,
+    pub variables: Vec<String>,
+    pub functions: Vec<String>,
+}
+
+/// Render the template, also returning each tag's rendered value and what it references
+pub async fn parse_and_render_tags<T: TemplateCallback>(
+    template: &str,
+    vars: &HashMap<String, String>,
+    cb: &T,
+    opt: &RenderOptions,
+) -> error::Result<(String, Vec<RenderedTag>)> {
     let mut p = Parser::new(template);
-    let tokens = p.parse();
-    render(tokens, vars, cb).await
+    let tokens = if opt.strict {
+        p.parse_strict().map_err(|e| ParseErr(e[0].clone()))?
+    } else {
+        p.parse()
+    };
+    let mut tags = Vec::new();
+    let rendered = render_tokens(tokens, p.tag_positions(), vars, cb, opt, Some(&mut tags)).await?;
+    Ok((rendered, tags))
 }
 
 pub async fn render<T: TemplateCallback>(
     tokens: Tokens,
     vars: &HashMap<String, String>,
     cb: &T,
-) -> String {
+    opt: &RenderOptions,
+) -> error::Result<String> {
+    render_tokens(tokens, &[], vars, cb, opt, None).await
+}
+
+async fn render_tokens<T: TemplateCallback>(
+    tokens: Tokens,
+    tag_positions: &[usize],
+    vars: &HashMap<String, String>,
+    cb: &T,
+    opt: &RenderOptions,
+    mut rendered_tags: Option<&mut Vec<RenderedTag>>,
+) -> error::Result<String> {
     let mut doc_str: Vec<String> = Vec::new();
+    let mut tag_positions = tag_positions.iter();
 
     for t in tokens.tokens {
         match t {
             Token::Raw { text } => doc_str.push(text),
-            Token::Tag { val } => doc_str.push(render_tag(val, &vars, cb).await),
+            Token::Escaped => doc_str.push("${[".to_string()),
+            Token::Tag { val } => {
+                let position = tag_positions.next().cloned();
+                let value = match rendered_tags.as_mut() {
+                    None => render_tag(val, vars, cb, opt, position).await?,
+                    Some(rendered_tags) => {
+                        let tag_tokens = Tokens {
+                            tokens: vec![Token::Tag { val: val.clone() }],
+                        };
+                        let tag = val.to_string();
+                        let value = render_tag(val, vars, cb, opt, position).await?;
+                        rendered_tags.push(RenderedTag {
+                            tag,
+                            position: position.unwrap_or_default(),
+                            value: value.clone(),
+                            variables: referenced_variables(&tag_tokens),
+                            functions: referenced_functions(&tag_tokens),
+                        });
+                        value
+                    }
+                };
+                doc_str.push(value)
+            }
             Token::Eof => {}
         }
     }
 
-    doc_str.join("")
+    Ok(doc_str.join(""))
 }
 
+/// Variables prefixed with this are read from the process environment, when the app
+/// defines them
+pub const PROCESS_ENV_PREFIX: &str = "env.";
+
 async fn render_tag<T: TemplateCallback>(
     val: Val,
     vars: &HashMap<String, String>,
     cb: &T,
-) -> String {
-    match val {
-        Val::Str { text } => text.into(),
+    opt: &RenderOptions,
+    position: Option<usize>,
+) -> error::Result<String> {
+    let v = render_value(val, vars, cb, opt, position).await?;
+    Ok(value_to_string(v))
+}
+
+async fn render_value<T: TemplateCallback>(
+    val: Val,
+    vars: &HashMap<String, String>,
+    cb: &T,
+    opt: &RenderOptions,
+    position: Option<usize>,
+) -> error::Result<Value> {
+    let v = match val {
+        Val::Str { text } => Value::String(text),
         Val::Var { name } => match vars.get(name.as_str()) {
-            Some(v) => v.to_string(),
-            None => "".into(),
+            Some(v) => Value::String(v.to_string()),
+            None if opt.strict => return Err(VariableNotDefinedErr { name, position }),
+            None => Value::String("".into()),
         },
-        Val::Bool { value } => value.to_string(),
+        Val::Bool { value } => Value::Bool(value),
+        Val::Num { value } => number_to_value(value),
+        Val::Arr { items } => {
+            let mut values = Vec::new();
+            for v in items {
+                values.push(Box::pin(render_value(v, vars, cb, opt, position)).await?);
+            }
+            Value::Array(values)
+        }
+        Val::Obj { entries } => {
+            let mut values = Map::new();
+            for e in entries {
+                let v = Box::pin(render_value(e.value, vars, cb, opt, position)).await?;
+                values.insert(e.key, v);
+            }
+            Value::Object(values)
+        }
         Val::Fn { name, args } => {
-            let empty = "".to_string();
-            let mut resolved_args: HashMap<String, String> = HashMap::new();
-            for a in args {
-                let (k, v) = match a {
-                    FnArg {
-                        name,
-                        value: Val::Str { text },
-                    } => (name.to_string(), text.to_string()),
-                    FnArg {
-                        name,
-                        value: Val::Var { name: var_name },
-                    } => (
-                        name.to_string(),
-                        vars.get(var_name.as_str()).unwrap_or(&empty).to_string(),
-                    ),
-                    FnArg { name, value: val } => {
-                        let r = Box::pin(render_tag(val.clone(), vars, cb)).await;
-                        (name.to_string(), r)
-                    }
-                };
-                resolved_args.insert(k, v);
+            Value::String(call_fn(name, None, args, vars, cb, opt, position).await?)
+        }
+        Val::Pipe { val, filters } => {
+            let mut v = Box::pin(render_tag(*val, vars, cb, opt, position)).await?;
+            for f in filters {
+                v = call_fn(f.name, Some(v), f.args, vars, cb, opt, position).await?;
             }
-            match cb.run(name.as_str(), resolved_args.clone()).await {
-                Ok(s) => s,
-                Err(e) => {
-                    warn!(
-                        "Failed to run template callback {}({:?}): {}",
-                        name, resolved_args, e
-                    );
-                    "".to_string()
+            Value::String(v)
+        }
+        Val::Coalesce { val, fallback } => {
+            // Undefined variables are allowed here, even in strict mode
+            let v = match *val {
+                Val::Var { name } if !vars.contains_key(name.as_str()) => Value::Null,
+                val => Box::pin(render_value(val, vars, cb, opt, position)).await?,
+            };
+            match v {
+                Value::Null => Box::pin(render_value(*fallback, vars, cb, opt, position)).await?,
+                Value::String(s) if s.is_empty() => {
+                    Box::pin(render_value(*fallback, vars, cb, opt, position)).await?
                 }
+                v => v,
             }
         }
-        Val::Null => "".into(),
+        Val::Null => Value::Null,
+    };
+
+    Ok(v)
+}
+
+/// Whole numbers are kept as integers so functions can read them with `as_i64()`
+fn number_to_value(n: f64) -> Value {
+    if n.fract() == 0.0 && n.abs() < i64::MAX as f64 {
+        Value::from(n as i64)
+    } else {
+        Value::from(n)
     }
 }
 
+fn value_to_string(v: Value) -> String {
+    match v {
+        Value::String(s) => s,
+        Value::Null => "".to_string(),
+        v => v.to_string(),
+    }
+}
+
+async fn call_fn<T: TemplateCallback>(
+    name: String,
+    input: Option<String>,
+    args: Vec<FnArg>,
+    vars: &HashMap<String, String>,
+    cb: &T,
+    opt: &RenderOptions,
+    position: Option<usize>,
+) -> error::Result<String> {
+    // A piped value is always the first positional argument
+    let mut positional_args: Vec<Value> = input.into_iter().map(Value::String).collect();
+    let mut resolved_args: HashMap<String, Value> = HashMap::new();
+    for a in args {
+        let v = Box::pin(render_value(a.value, vars, cb, opt, position)).await?;
+        if a.name.is_empty() {
+            positional_args.push(v);
+        } else {
+            resolved_args.insert(a.name, v);
+        }
+    }
+
+    let result = match resolve_positional_args(&name, positional_args, cb).await {
+        Ok(positional_args) => {
+            for (k, v) in positional_args {
+                resolved_args.entry(k).or_insert(v);
+            }
+            cb.run(name.as_str(), resolved_args.clone()).await
+        }
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(s) => Ok(s),
+        Err(message) if opt.strict => Err(FunctionErr {
+            name,
+            message,
+            position,
+        }),
+        Err(e) => {
+            warn!(
+                "Failed to run template callback {}({:?}): {}",
+                name, resolved_args, e
+            );
+            Ok("".to_string())
+        }
+    }
+}
+
+async fn resolve_positional_args<T: TemplateCallback>(
+    fn_name: &str,
+    args: Vec<Value>,
+    cb: &T,
+) -> Result<Vec<(String, Value)>, String> {
+    if args.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let names = cb.arg_names(fn_name).await;
+    if args.len() > names.len() {
+        return Err(format!(
+            "Expected at most {} positional arguments but got {}",
+            names.len(),
+            args.len()
+        ));
+    }
+
+    Ok(names.into_iter().zip(args).collect())
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::error::Error::{FunctionErr, ParseErr, VariableNotDefinedErr};
     use crate::renderer::TemplateCallback;
     use crate::*;
+    use serde_json::Value;
     use std::collections::HashMap;
 
     struct EmptyCB {}
@@ -101,7 +306,7 @@ mod tests {
         async fn run(
             &self,
             _fn_name: &str,
-            _args: HashMap<String, String>,
+            _args: HashMap<String, Value>,
         ) -> Result<String, String> {
             todo!()
         }
@@ -114,8 +319,8 @@ mod tests {
         let vars = HashMap::new();
         let result = "";
         assert_eq!(
-            parse_and_render(template, &vars, &empty_cb).await,
-            result.to_string()
+            parse_and_render(template, &vars, &empty_cb, &RenderOptions::default()).await,
+            Ok(result.to_string())
         );
     }
 
@@ -126,8 +331,8 @@ mod tests {
         let vars = HashMap::new();
         let result = "Hello World!";
         assert_eq!(
-            parse_and_render(template, &vars, &empty_cb).await,
-            result.to_string()
+            parse_and_render(template, &vars, &empty_cb, &RenderOptions::default()).await,
+            Ok(result.to_string())
         );
     }
 
@@ -138,8 +343,8 @@ mod tests {
         let vars = HashMap::from([("foo".to_string(), "bar".to_string())]);
         let result = "bar";
         assert_eq!(
-            parse_and_render(template, &vars, &empty_cb).await,
-            result.to_string()
+            parse_and_render(template, &vars, &empty_cb, &RenderOptions::default()).await,
+            Ok(result.to_string())
         );
     }
 
@@ -150,8 +355,8 @@ mod tests {
         let vars = HashMap::from([("word".to_string(), "cruel".to_string())]);
         let result = "hello cruel world!";
         assert_eq!(
-            parse_and_render(template, &vars, &empty_cb).await,
-            result.to_string()
+            parse_and_render(template, &vars, &empty_cb, &RenderOptions::default()).await,
+            Ok(result.to_string())
         );
     }
 
@@ -166,17 +371,20 @@ mod tests {
             async fn run(
                 &self,
                 fn_name: &str,
-                args: HashMap<String, String>,
+                args: HashMap<String, Value>,
             ) -> Result<String, String> {
                 Ok(format!(
                     "{fn_name}: {}, {:?} {:?}",
                     args.len(),
-                    args.get("a"),
-                    args.get("b")
+                    args.get("a").and_then(|v| v.as_str()),
+                    args.get("b").and_then(|v| v.as_str())
                 ))
             }
         }
-        assert_eq!(parse_and_render(template, &vars, &CB {}).await, result);
+        assert_eq!(
+            parse_and_render(template, &vars, &CB {}, &RenderOptions::default()).await,
+            Ok(result.to_string())
+        );
     }
 
     #[tokio::test]
@@ -189,19 +397,19 @@ mod tests {
             async fn run(
                 &self,
                 fn_name: &str,
-                args: HashMap<String, String>,
+                args: HashMap<String, Value>,
             ) -> Result<String, String> {
                 Ok(match fn_name {
                     "secret" => "abc".to_string(),
-                    "upper" => args["foo"].to_string().to_uppercase(),
+                    "upper" => args["foo"].as_str().unwrap().to_uppercase(),
                     _ => "".to_string(),
                 })
             }
         }
 
         assert_eq!(
-            parse_and_render(template, &vars, &CB {}).await,
-            result.to_string()
+            parse_and_render(template, &vars, &CB {}, &RenderOptions::default()).await,
+            Ok(result.to_string())
         );
     }
 
@@ -216,15 +424,243 @@ mod tests {
             async fn run(
                 &self,
                 _fn_name: &str,
-                _args: HashMap<String, String>,
+                _args: HashMap<String, Value>,
+            ) -> Result<String, String> {
+                Err("Failed to do it!".to_string())
+            }
+        }
+
+        assert_eq!(
+            parse_and_render(template, &vars, &CB {}, &RenderOptions::default()).await,
+            Ok(result.to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn render_tags() {
+        let template = "${[ base ]}/${[ upper(v=path) ]}";
+        let vars = HashMap::from([
+            ("base".to_string(), "https://example.com".to_string()),
+            ("path".to_string(), "users".to_string()),
+        ]);
+
+        struct CB {}
+        impl TemplateCallback for CB {
+            async fn run(
+                &self,
+                _fn_name: &str,
+                args: HashMap<String, Value>,
+            ) -> Result<String, String> {
+                Ok(args["v"].as_str().unwrap().to_uppercase())
+            }
+        }
+
+        let (rendered, tags) =
+            parse_and_render_tags(template, &vars, &CB {}, &RenderOptions::default())
+                .await
+                .unwrap();
+        assert_eq!(rendered, "https://example.com/USERS");
+        assert_eq!(
+            tags,
+            vec![
+                RenderedTag {
+                    tag: "base".to_string(),
+                    position: 0,
+                    value: "https://example.com".to_string(),
+                    variables: vec!["base".to_string()],
+                    functions: vec![],
+                },
+                RenderedTag {
+                    tag: "upper(v=path)".to_string(),
+                    position: 12,
+                    value: "USERS".to_string(),
+                    variables: vec!["path".to_string()],
+                    functions: vec!["upper".to_string()],
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn render_strict_undefined_var() {
+        let empty_cb = EmptyCB {};
+        let template = "hello ${[ word ]}";
+        let vars = HashMap::new();
+        assert_eq!(
+            parse_and_render(template, &vars, &empty_cb, &RenderOptions::strict()).await,
+            Err(VariableNotDefinedErr {
+                name: "word".to_string(),
+                position: Some(6),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn render_strict_fn_err() {
+        let vars = HashMap::new();
+        let template = r#"${[ upper(foo=error()) ]}"#;
+
+        struct CB {}
+        impl TemplateCallback for CB {
+            async fn run(
+                &self,
+                _fn_name: &str,
+                _args: HashMap<String, Value>,
             ) -> Result<String, String> {
                 Err("Failed to do it!".to_string())
             }
         }
 
+        let result = parse_and_render(template, &vars, &CB {}, &RenderOptions::strict()).await;
+        assert_eq!(
+            result,
+            Err(FunctionErr {
+                name: "error".to_string(),
+                message: "Failed to do it!".to_string(),
+                position: Some(0),
+            })
+        );
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Failed to run template function error() at position 0: Failed to do it!"
+        );
+    }
+
+    #[tokio::test]
+    async fn render_strict_parse_err() {
+        let empty_cb = EmptyCB {};
+        let template = "${[ foo( ]}";
+        let vars = HashMap::new();
+        let result = parse_and_render(template, &vars, &empty_cb, &RenderOptions::strict()).await;
+        assert!(matches!(result, Err(ParseErr(_))));
+    }
+
+    #[tokio::test]
+    async fn render_pipe() {
+        let vars = HashMap::from([("name".to_string(), "John".to_string())]);
+        let template = r#"${[ name | greet('Hi') | upper ]}"#;
+        let result = r#"HI JOHN"#;
+
+        struct CB {}
+        impl TemplateCallback for CB {
+            async fn run(
+                &self,
+                fn_name: &str,
+                args: HashMap<String, Value>,
+            ) -> Result<String, String> {
+                Ok(match fn_name {
+                    "greet" => format!(
+                        "{} {}",
+                        args["greeting"].as_str().unwrap(),
+                        args["name"].as_str().unwrap()
+                    ),
+                    "upper" => args["value"].as_str().unwrap().to_uppercase(),
+                    _ => "".to_string(),
+                })
+            }
+
+            async fn arg_names(&self, fn_name: &str) -> Vec<String> {
+                match fn_name {
+                    "greet" => vec!["name".to_string(), "greeting".to_string()],
+                    "upper" => vec!["value".to_string()],
+                    _ => Vec::new(),
+                }
+            }
+        }
+
+        assert_eq!(
+            parse_and_render(template, &vars, &CB {}, &RenderOptions::default()).await,
+            Ok(result.to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn render_pipe_too_many_args() {
+        let empty_cb = EmptyCB {};
+        let template = r#"${[ 'foo' | upper ]}"#;
+        let vars = HashMap::new();
+        assert_eq!(
+            parse_and_render(template, &vars, &empty_cb, &RenderOptions::strict()).await,
+            Err(FunctionErr {
+                name: "upper".to_string(),
+                message: "Expected at most 0 positional arguments but got 1".to_string(),
+                position: Some(0),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn render_typed_args() {
+        let vars = HashMap::from([("foo".to_string(), "bar".to_string())]);
+        let template = r#"${[ json(n=3, f=-1.5, b=true, l=[1, foo], o={'a': null}) ]}"#;
+        let result = r#"{"b":true,"f":-1.5,"l":[1,"bar"],"n":3,"o":{"a":null}}"#;
+
+        struct CB {}
+        impl TemplateCallback for CB {
+            async fn run(
+                &self,
+                _fn_name: &str,
+                args: HashMap<String, Value>,
+            ) -> Result<String, String> {
+                assert_eq!(args["n"].as_i64(), Some(3));
+                let args: serde_json::Map<String, Value> = args.into_iter().collect();
+                Ok(Value::Object(args).to_string())
+            }
+        }
+
+        assert_eq!(
+            parse_and_render(template, &vars, &CB {}, &RenderOptions::default()).await,
+            Ok(result.to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn render_literals() {
+        let empty_cb = EmptyCB {};
+        let template = r#"${[ 42 ]} ${[ [1, 'a'] ]} ${[ {'a': {}} ]}"#;
+        let vars = HashMap::new();
+        let result = r#"42 [1,"a"] {"a":{}}"#;
+        assert_eq!(
+            parse_and_render(template, &vars, &empty_cb, &RenderOptions::default()).await,
+            Ok(result.to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn render_coalesce() {
+        let vars = HashMap::from([("foo".to_string(), "bar".to_string())]);
+        let template =
+            r#"${[ foo ?? 'x' ]} ${[ missing ?? 'x' ]} ${[ empty() ?? missing ?? foo ]}"#;
+        let result = r#"bar x bar"#;
+
+        struct CB {}
+        impl TemplateCallback for CB {
+            async fn run(
+                &self,
+                _fn_name: &str,
+                _args: HashMap<String, Value>,
+            ) -> Result<String, String> {
+                Ok("".to_string())
+            }
+        }
+
+        assert_eq!(
+            parse_and_render(template, &vars, &CB {}, &RenderOptions::strict()).await,
+            Ok(result.to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn render_coalesce_strict_undefined_fallback() {
+        let empty_cb = EmptyCB {};
+        let template = "${[ a ?? b ]}";
+        let vars = HashMap::new();
         assert_eq!(
-            parse_and_render(template, &vars, &CB {}).await,
-            result.to_string()
+            parse_and_render(template, &vars, &empty_cb, &RenderOptions::strict()).await,
+            Err(VariableNotDefinedErr {
+                name: "b".to_string(),
+                position: Some(0),
+            })
         );
     }
 }
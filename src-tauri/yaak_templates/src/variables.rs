@@ -0,0 +1,207 @@
+use crate::error;
+use crate::error::Error::VariableCycleErr;
+use crate::{parse_and_render, referenced_variables, Parser, RenderOptions, TemplateCallback};
+use log::warn;
+use std::collections::HashMap;
+
+#[derive(PartialEq)]
+enum VisitState {
+    Visiting,
+    Done,
+}
+
+/// Render the variables a template uses, along with the variables they reference in turn.
+/// Dependencies are resolved first so each variable (and any function calls in it) is
+/// rendered exactly once. Variables the template doesn't reach aren't rendered, so they
+/// can't fail the render. Cycles are an error in strict mode, and otherwise render the
+/// reference that closes the cycle as undefined.
+pub async fn resolve_variables<T: TemplateCallback>(
+    vars: &HashMap<String, String>,
+    used: &[String],
+    cb: &T,
+    opt: &RenderOptions,
+) -> error::Result<HashMap<String, String>> {
+    let deps: HashMap<&str, Vec<String>> = vars
+        .iter()
+        .map(|(name, value)| {
+            let refs = referenced_variables(&Parser::new(value).parse())
+                .into_iter()
+                .filter(|r| vars.contains_key(r))
+                .collect();
+            (name.as_str(), refs)
+        })
+        .collect();
+
+    // Sort for a deterministic order, so the same cycle is always reported
+    let mut names = used
+        .iter()
+        .filter_map(|n| vars.get_key_value(n).map(|(k, _)| k.as_str()))
+        .collect::<Vec<&str>>();
+    names.sort();
+
+    let mut order = Vec::new();
+    let mut state = HashMap::new();
+    for name in names {
+        visit(name, &deps, &mut state, &mut Vec::new(), &mut order, opt)?;
+    }
+
+    let mut resolved = HashMap::new();
+    for name in order {
+        let value = parse_and_render(&vars[name], &resolved, cb, opt).await?;
+        resolved.insert(name.to_string(), value);
+    }
+
+    Ok(resolved)
+}
+
+fn visit<'a>(
+    name: &'a str,
+    deps: &'a HashMap<&'a str, Vec<String>>,
+    state: &mut HashMap<&'a str, VisitState>,
+    path: &mut Vec<&'a str>,
+    order: &mut Vec<&'a str>,
+    opt: &RenderOptions,
+) -> error::Result<()> {
+    match state.get(name) {
+        Some(VisitState::Done) => return Ok(()),
+        Some(VisitState::Visiting) => {
+            let start = path.iter().position(|n| *n == name).unwrap_or_default();
+            let mut cycle: Vec<String> = path[start..].iter().map(|n| n.to_string()).collect();
+            cycle.push(name.to_string());
+            if opt.strict {
+                return Err(VariableCycleErr { cycle });
+            }
+            warn!("Variable cycle detected: {}", cycle.join(" -> "));
+            return Ok(());
+        }
+        None => {}
+    }
+
+    state.insert(name, VisitState::Visiting);
+    path.push(name);
+    for dep in &deps[name] {
+        visit(dep.as_str(), deps, state, path, order, opt)?;
+    }
+    path.pop();
+    state.insert(name, VisitState::Done);
+    order.push(name);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::Error::{VariableCycleErr, VariableNotDefinedErr};
+    use crate::*;
+    use serde_json::Value;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct CountingCB {
+        calls: AtomicUsize,
+    }
+
+    impl TemplateCallback for CountingCB {
+        async fn run(
+            &self,
+            _fn_name: &str,
+            _args: HashMap<String, Value>,
+        ) -> Result<String, String> {
+            let n = self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(n.to_string())
+        }
+    }
+
+    fn vars(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn resolve_deep_chain() {
+        let cb = CountingCB {
+            calls: AtomicUsize::new(0),
+        };
+        let vars = vars(&[
+            ("a", "${[ b ]}!"),
+            ("b", "${[ c ]}!"),
+            ("c", "${[ d ]}!"),
+            ("d", "${[ e ]}!"),
+            ("e", "${[ f ]}!"),
+            ("f", "end"),
+        ]);
+        let resolved = resolve_variables(&vars, &["a".into()], &cb, &RenderOptions::strict())
+            .await
+            .unwrap();
+        assert_eq!(resolved["a"], "end!!!!!");
+    }
+
+    #[tokio::test]
+    async fn resolve_fn_once() {
+        let cb = CountingCB {
+            calls: AtomicUsize::new(0),
+        };
+        let vars = vars(&[
+            ("token", "${[ fetch_token() ]}"),
+            ("auth", "Bearer ${[ token ]}"),
+            ("other", "${[ token ]}"),
+        ]);
+        let used = ["auth".into(), "other".into()];
+        let resolved = resolve_variables(&vars, &used, &cb, &RenderOptions::strict())
+            .await
+            .unwrap();
+        assert_eq!(resolved["auth"], "Bearer 0");
+        assert_eq!(resolved["other"], "0");
+        assert_eq!(cb.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn resolve_cycle() {
+        let cb = CountingCB {
+            calls: AtomicUsize::new(0),
+        };
+        let vars = vars(&[("a", "${[ b ]}"), ("b", "${[ c ]}"), ("c", "${[ a ]}")]);
+        assert_eq!(
+            resolve_variables(&vars, &["a".into()], &cb, &RenderOptions::strict()).await,
+            Err(VariableCycleErr {
+                cycle: vec!["a".into(), "b".into(), "c".into(), "a".into()]
+            })
+        );
+
+        let resolved = resolve_variables(&vars, &["a".into()], &cb, &RenderOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(resolved["a"], "");
+    }
+
+    #[tokio::test]
+    async fn resolve_strict_chain() {
+        let cb = CountingCB {
+            calls: AtomicUsize::new(0),
+        };
+        let vars = vars(&[
+            ("a", "${[ b ]}"),
+            ("b", "${[ c ]}"),
+            ("c", "${[ missing ]}"),
+            ("ok", "fine"),
+        ]);
+        assert_eq!(
+            resolve_variables(&vars, &["a".into()], &cb, &RenderOptions::strict()).await,
+            Err(VariableNotDefinedErr {
+                name: "missing".into(),
+                position: Some(0),
+            })
+        );
+
+        // Variables the template doesn't reach can't fail it
+        let resolved = resolve_variables(&vars, &["ok".into()], &cb, &RenderOptions::strict())
+            .await
+            .unwrap();
+        assert_eq!(
+            resolved,
+            HashMap::from([("ok".to_string(), "fine".to_string())])
+        );
+    }
+}
@@ -5,7 +5,7 @@ import { useActiveRequest } from '../hooks/useActiveRequest';
 import { useGrpc } from '../hooks/useGrpc';
 import { useGrpcConnections } from '../hooks/useGrpcConnections';
 import { useGrpcEvents } from '../hooks/useGrpcEvents';
-import { useGrpcProtoFiles } from '../hooks/useGrpcProtoFiles';
+import { useGrpcProtoFiles, useGrpcProtoIncludes } from '../hooks/useGrpcProtoFiles';
 import { useUpdateAnyGrpcRequest } from '../hooks/useUpdateAnyGrpcRequest';
 import { Banner } from './core/Banner';
 import { HotKeyList } from './core/HotKeyList';
@@ -27,9 +27,11 @@ export function GrpcConnectionLayout({ style }: Props) {
   const messages = useGrpcEvents(activeConnection?.id ?? null);
   const protoFilesKv = useGrpcProtoFiles(activeRequest?.id ?? null);
   const protoFiles = protoFilesKv.value ?? emptyArray;
-  const grpc = useGrpc(activeRequest, activeConnection, protoFiles);
+  const protoIncludesKv = useGrpcProtoIncludes(activeRequest?.id ?? null);
+  const protoIncludes = protoIncludesKv.value ?? emptyArray;
+  const grpc = useGrpc(activeRequest, activeConnection, protoFiles, protoIncludes);
 
-  const services = grpc.reflect.data ?? null;
+  const services = grpc.reflect.data?.services ?? null;
   useEffect(() => {
     if (services == null || activeRequest == null) return;
     const s = services.find((s) => s.name === activeRequest.service);
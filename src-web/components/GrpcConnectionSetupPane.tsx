@@ -46,6 +46,12 @@ interface Props {
 
 const useActiveTab = createGlobalState<string>('message');
 
+const protocolOptions: { label: string; value: GrpcRequest['protocol'] }[] = [
+  { label: 'gRPC', value: 'grpc' },
+  { label: 'gRPC-Web', value: 'grpc_web' },
+  { label: 'gRPC-Web Text', value: 'grpc_web_text' },
+];
+
 export function GrpcConnectionSetupPane({
   style,
   services,
@@ -75,6 +81,12 @@ export function GrpcConnectionSetupPane({
     [activeRequest.id, updateRequest],
   );
 
+  const handleChangeProtocol = useCallback(
+    (protocol: GrpcRequest['protocol']) =>
+      updateRequest.mutateAsync({ id: activeRequest.id, update: { protocol } }),
+    [activeRequest.id, updateRequest],
+  );
+
   const handleChangeMessage = useCallback(
     (message: string) => {
       return updateRequest.mutateAsync({ id: activeRequest.id, update: { message } });
@@ -197,6 +209,25 @@ export function GrpcConnectionSetupPane({
           isLoading={isStreaming}
         />
         <HStack space={1.5}>
+          <RadioDropdown<GrpcRequest['protocol']>
+            value={activeRequest.protocol || 'grpc'}
+            onChange={handleChangeProtocol}
+            items={protocolOptions.map((o) => ({
+              ...o,
+              type: 'default' as const,
+              shortLabel: o.label,
+            }))}
+          >
+            <Button
+              size="sm"
+              variant="border"
+              rightSlot={<Icon className="text-text-subtlest" size="sm" icon="chevronDown" />}
+              disabled={isStreaming}
+              className="font-mono text-editor !ring-0"
+            >
+              {protocolOptions.find((o) => o.value === (activeRequest.protocol || 'grpc'))?.label}
+            </Button>
+          </RadioDropdown>
           <RadioDropdown
             value={select.value}
             onChange={handleChangeService}
@@ -1,6 +1,6 @@
 import { open } from '@tauri-apps/plugin-dialog';
 import { useGrpc } from '../hooks/useGrpc';
-import { useGrpcProtoFiles } from '../hooks/useGrpcProtoFiles';
+import { useGrpcProtoFiles, useGrpcProtoIncludes } from '../hooks/useGrpcProtoFiles';
 import { useGrpcRequest } from '../hooks/useGrpcRequest';
 import { count } from '../lib/pluralize';
 import { Banner } from './core/Banner';
@@ -19,8 +19,10 @@ export function GrpcProtoSelection({ requestId }: Props) {
   const request = useGrpcRequest(requestId);
   const protoFilesKv = useGrpcProtoFiles(requestId);
   const protoFiles = protoFilesKv.value ?? [];
-  const grpc = useGrpc(request, null, protoFiles);
-  const services = grpc.reflect.data;
+  const protoIncludesKv = useGrpcProtoIncludes(requestId);
+  const protoIncludes = protoIncludesKv.value ?? [];
+  const grpc = useGrpc(request, null, protoFiles, protoIncludes);
+  const services = grpc.reflect.data?.services;
   const serverReflection = protoFiles.length === 0 && services != null;
   let reflectError = grpc.reflect.error ?? null;
   const reflectionUnimplemented = `${reflectError}`.match(/unimplemented/i);
@@ -43,7 +45,7 @@ export function GrpcProtoSelection({ requestId }: Props) {
             const selected = await open({
               title: 'Select Proto Files',
               multiple: true,
-              filters: [{ name: 'Proto Files', extensions: ['proto'] }],
+              filters: [{ name: 'Proto Files', extensions: ['proto', 'protoset', 'binpb'] }],
             });
             if (selected == null) return;
 
@@ -54,6 +56,21 @@ export function GrpcProtoSelection({ requestId }: Props) {
         >
           Add File
         </Button>
+        <Button
+          color="secondary"
+          onClick={async () => {
+            const selected = await open({
+              title: 'Select Import Directory',
+              directory: true,
+            });
+            if (selected == null || protoIncludes.includes(selected)) return;
+
+            await protoIncludesKv.set([...protoIncludes, selected]);
+            await grpc.reflect.refetch();
+          }}
+        >
+          Add Import Path
+        </Button>
         <Button
           isLoading={grpc.reflect.isFetching}
           disabled={grpc.reflect.isFetching}
@@ -92,6 +109,12 @@ export function GrpcProtoSelection({ requestId }: Props) {
                   </span>
                 );
               })}
+              {grpc.reflect.data?.reflectionVersion != null && (
+                <>
+                  {' '}
+                  using reflection <InlineCode>{grpc.reflect.data.reflectionVersion}</InlineCode>
+                </>
+              )}
               . You can override this schema by manually selecting <InlineCode>*.proto</InlineCode>{' '}
               files.
             </p>
@@ -127,6 +150,34 @@ export function GrpcProtoSelection({ requestId }: Props) {
             </tbody>
           </table>
         )}
+
+        {protoIncludes.length > 0 && (
+          <table className="w-full divide-y divide-surface-highlight">
+            <thead>
+              <tr>
+                <th className="text-text-subtlest">Import Paths</th>
+                <th></th>
+              </tr>
+            </thead>
+            <tbody className="divide-y divide-surface-highlight">
+              {protoIncludes.map((d, i) => (
+                <tr key={d + i} className="group">
+                  <td className="pl-1 font-mono">{d}</td>
+                  <td className="w-0 py-0.5">
+                    <IconButton
+                      title="Remove import path"
+                      icon="trash"
+                      className="ml-auto opacity-50 transition-opacity group-hover:opacity-100"
+                      onClick={async () => {
+                        await protoIncludesKv.set(protoIncludes.filter((p) => p !== d));
+                      }}
+                    />
+                  </td>
+                </tr>
+              ))}
+            </tbody>
+          </table>
+        )}
         {reflectError && (
           <Banner color="warning">
             <h1 className="font-bold">
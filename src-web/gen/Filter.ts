@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { FnArg } from "./FnArg";
+
+export type Filter = { name: string, args: Array<FnArg>, };
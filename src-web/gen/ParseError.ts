@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type ParseError = { message: string, 
+/**
+ * Character offset where the error starts
+ */
+start: number, 
+/**
+ * Character offset where the error ends (exclusive)
+ */
+end: number, expected: string | null, };
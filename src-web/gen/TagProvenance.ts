@@ -0,0 +1,17 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { TagSource } from "./TagSource";
+
+export type TagProvenance = { 
+/**
+ * The tag's expression, without the surrounding `${[ ]}`
+ */
+tag: string, 
+/**
+ * Character offset of the tag in the template
+ */
+position: number, value: string, sources: Array<TagSource>, 
+/**
+ * How each of the tag's variables was resolved, as one path per chain of variables
+ * referencing each other, like `a` → `b` → `env.HOME`
+ */
+resolution_paths: Array<Array<TagSource>>, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Where a value used by a tag came from
+ */
+export type TagSource = { "type": "workspace", workspace_id: string, workspace_name: string, variable: string, } | { "type": "environment", environment_id: string, environment_name: string, variable: string, } | { "type": "folder", folder_id: string, folder_name: string, variable: string, } | { "type": "request", variable: string, } | { "type": "plugin", plugin: string, function: string, } | { "type": "native", function: string, } | { "type": "process_env", variable: string, } | { "type": "env_file", environment_id: string, environment_name: string, path: string, variable: string, } | { "type": "unknown", name: string, };
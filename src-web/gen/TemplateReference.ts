@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type TemplateReference = { name: string, 
+/**
+ * Where the reference appears, like `url` or `headers.Authorization`
+ */
+locations: Array<string>, 
+/**
+ * Whether a variable or function with this name exists
+ */
+defined: boolean, };
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { TemplateReference } from "./TemplateReference";
+
+export type TemplateReferences = { variables: Array<TemplateReference>, functions: Array<TemplateReference>, 
+/**
+ * Defined variables that nothing references, directly or through other variables
+ */
+unused_variables: Array<string>, };
@@ -1,4 +1,4 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { Val } from "./Val";
 
-export type Token = { "type": "raw", text: string, } | { "type": "tag", val: Val, } | { "type": "eof" };
+export type Token = { "type": "raw", text: string, } | { "type": "escaped" } | { "type": "tag", val: Val, } | { "type": "eof" };
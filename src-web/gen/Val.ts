@@ -1,4 +1,6 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Filter } from "./Filter";
 import type { FnArg } from "./FnArg";
+import type { ObjEntry } from "./ObjEntry";
 
-export type Val = { "type": "str", text: string, } | { "type": "var", name: string, } | { "type": "bool", value: boolean, } | { "type": "fn", name: string, args: Array<FnArg>, } | { "type": "null" };
+export type Val = { "type": "str", text: string, } | { "type": "var", name: string, } | { "type": "bool", value: boolean, } | { "type": "num", value: number, } | { "type": "arr", items: Array<Val>, } | { "type": "obj", entries: Array<ObjEntry>, } | { "type": "fn", name: string, args: Array<FnArg>, } | { "type": "pipe", val: Val, filters: Array<Filter>, } | { "type": "coalesce", val: Val, fallback: Val, } | { "type": "null" };
@@ -5,7 +5,12 @@ import { invokeCmd } from '../lib/tauri';
 import { useActiveEnvironment } from './useActiveEnvironment';
 import { useActiveWorkspace } from './useActiveWorkspace';
 import { useAppRoutes } from './useAppRoutes';
-import { getGrpcProtoFiles, setGrpcProtoFiles } from './useGrpcProtoFiles';
+import {
+  getGrpcProtoFiles,
+  getGrpcProtoIncludes,
+  setGrpcProtoFiles,
+  setGrpcProtoIncludes,
+} from './useGrpcProtoFiles';
 
 export function useDuplicateGrpcRequest({
   id,
@@ -31,6 +36,8 @@ export function useDuplicateGrpcRequest({
       // Also copy proto files to new request
       const protoFiles = await getGrpcProtoFiles(id);
       await setGrpcProtoFiles(request.id, protoFiles);
+      const protoIncludes = await getGrpcProtoIncludes(id);
+      await setGrpcProtoIncludes(request.id, protoIncludes);
 
       if (navigateAfter && activeWorkspace !== null) {
         routes.navigate('request', {
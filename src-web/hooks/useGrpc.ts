@@ -12,10 +12,16 @@ export interface ReflectResponseService {
   methods: { name: string; schema: string; serverStreaming: boolean; clientStreaming: boolean }[];
 }
 
+export interface ReflectResponse {
+  services: ReflectResponseService[];
+  reflectionVersion: 'v1' | 'v1alpha' | null;
+}
+
 export function useGrpc(
   req: GrpcRequest | null,
   conn: GrpcConnection | null,
   protoFiles: string[],
+  protoIncludes: string[],
 ) {
   const requestId = req?.id ?? 'n/a';
   const [environment] = useActiveEnvironment();
@@ -23,7 +29,12 @@ export function useGrpc(
   const go = useMutation<void, string>({
     mutationKey: ['grpc_go', conn?.id],
     mutationFn: async () =>
-      await invokeCmd('cmd_grpc_go', { requestId, environmentId: environment?.id, protoFiles }),
+      await invokeCmd('cmd_grpc_go', {
+        requestId,
+        environmentId: environment?.id,
+        protoFiles,
+        protoIncludes,
+      }),
     onSettled: () => trackEvent('grpc_request', 'send'),
   });
 
@@ -47,16 +58,28 @@ export function useGrpc(
   });
 
   const debouncedUrl = useDebouncedValue<string>(req?.url ?? '', 1000);
-  const debouncedMessage = useDebouncedValue<string>(req?.message ?? '', 1000);
 
-  const reflect = useQuery<ReflectResponseService[], string>({
+  const reflect = useQuery<ReflectResponse, string>({
     enabled: req != null,
-    queryKey: ['grpc_reflect', req?.id ?? 'n/a', debouncedUrl, debouncedMessage, protoFiles],
+    queryKey: [
+      'grpc_reflect',
+      req?.id ?? 'n/a',
+      environment?.id,
+      req?.protocol,
+      debouncedUrl,
+      protoFiles,
+      protoIncludes,
+    ],
     queryFn: async () =>
       (await minPromiseMillis(
-        invokeCmd('cmd_grpc_reflect', { requestId, protoFiles }),
+        invokeCmd('cmd_grpc_reflect', {
+          requestId,
+          environmentId: environment?.id,
+          protoFiles,
+          protoIncludes,
+        }),
         300,
-      )) as ReflectResponseService[],
+      )) as ReflectResponse,
   });
 
   return {
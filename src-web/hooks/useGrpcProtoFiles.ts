@@ -19,3 +19,22 @@ export async function getGrpcProtoFiles(requestId: string) {
 export async function setGrpcProtoFiles(requestId: string, protoFiles: string[]) {
   return setKeyValue<string[]>({ ...protoFilesArgs(requestId), value: protoFiles });
 }
+
+export function protoIncludesArgs(requestId: string | null) {
+  return {
+    namespace: 'global' as const,
+    key: ['proto_includes', requestId ?? 'n/a'],
+  };
+}
+
+export function useGrpcProtoIncludes(activeRequestId: string | null) {
+  return useKeyValue<string[]>({ ...protoIncludesArgs(activeRequestId), fallback: [] });
+}
+
+export async function getGrpcProtoIncludes(requestId: string) {
+  return getKeyValue<string[]>({ ...protoIncludesArgs(requestId), fallback: [] });
+}
+
+export async function setGrpcProtoIncludes(requestId: string, protoIncludes: string[]) {
+  return setKeyValue<string[]>({ ...protoIncludesArgs(requestId), value: protoIncludes });
+}
@@ -1,15 +1,17 @@
 import { useQuery } from '@tanstack/react-query';
 import { invokeCmd } from '../lib/tauri';
 import { useActiveEnvironment } from './useActiveEnvironment';
+import { useActiveRequestId } from './useActiveRequestId';
 import { useActiveWorkspace } from './useActiveWorkspace';
 
 export function useRenderTemplate(template: string) {
   const workspaceId = useActiveWorkspace()?.id ?? 'n/a';
   const environmentId = useActiveEnvironment()[0]?.id ?? null;
+  const requestId = useActiveRequestId();
   return useQuery<string>({
     placeholderData: (prev) => prev, // Keep previous data on refetch
-    queryKey: ['render_template', template],
-    queryFn: () => renderTemplate({ template, workspaceId, environmentId }),
+    queryKey: ['render_template', template, requestId],
+    queryFn: () => renderTemplate({ template, workspaceId, environmentId, requestId }),
   });
 }
 
@@ -17,10 +19,12 @@ export async function renderTemplate({
   template,
   workspaceId,
   environmentId,
+  requestId,
 }: {
   template: string;
   workspaceId: string;
   environmentId: string | null;
+  requestId: string | null;
 }): Promise<string> {
-  return invokeCmd('cmd_render_template', { template, workspaceId, environmentId });
+  return invokeCmd('cmd_render_template', { template, workspaceId, environmentId, requestId });
 }
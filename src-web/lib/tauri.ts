@@ -34,6 +34,7 @@ type TauriCmd =
   | 'cmd_get_grpc_request'
   | 'cmd_get_http_request'
   | 'cmd_get_key_value'
+  | 'cmd_get_local_access'
   | 'cmd_get_settings'
   | 'cmd_get_workspace'
   | 'cmd_grpc_go'
@@ -56,13 +57,16 @@ type TauriCmd =
   | 'cmd_parse_template'
   | 'cmd_plugin_info'
   | 'cmd_render_template'
+  | 'cmd_render_template_provenance'
   | 'cmd_reload_plugins'
   | 'cmd_save_response'
   | 'cmd_send_ephemeral_request'
   | 'cmd_send_http_request'
   | 'cmd_set_key_value'
+  | 'cmd_set_local_access'
   | 'cmd_set_update_mode'
   | 'cmd_template_functions'
+  | 'cmd_template_references'
   | 'cmd_track_event'
   | 'cmd_update_cookie_jar'
   | 'cmd_update_environment'